@@ -0,0 +1,517 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use crate::dns_structs::name::Name;
+use crate::dns_structs::record::{Data, DNSRecord};
+use crate::dns_structs::text::write_hex;
+use crate::dns_structs::writer::{EncodeError, SliceWriter};
+use crate::enums::{Class, ExtendedErrorCode};
+use crate::Kind;
+
+/// The DO bit of the flags of the OPT record (RFC 3225).
+const DO_MASK: u32 = 1 << 15;
+
+/// The payload size advertised by default, the value agreed on by the DNS Flag Day 2020 to avoid IP fragmentation.
+pub const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The length of an OPT record without options: the root name, type, class, TTL and RDLENGTH.
+const OPT_RECORD_LEN: usize = 1 + 2 + 2 + 4 + 2;
+
+/// The option code of NSID (RFC 5001).
+const NSID: u16 = 3;
+/// The option code of EDNS Client Subnet (RFC 7871).
+const CLIENT_SUBNET: u16 = 8;
+/// The option code of DNS Cookies (RFC 7873).
+const COOKIE: u16 = 10;
+/// The option code of Padding (RFC 7830).
+const PADDING: u16 = 12;
+/// The option code of Extended DNS Errors (RFC 8914).
+const EXTENDED_ERROR: u16 = 15;
+
+/// An option carried in the data of an OPT record, as registered in the "DNS EDNS0 Option Codes
+/// (OPT)" IANA registry.
+///
+/// For more information, see [RFC 6891 section 6.1.2](https://www.rfc-editor.org/rfc/rfc6891#section-6.1.2).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EdnsOption {
+    /// The identifier of the name server that answered, empty in queries to ask for it (RFC 5001).
+    Nsid(Vec<u8>),
+    /// The network of the client the query is made on behalf of (RFC 7871).
+    ClientSubnet(ClientSubnet),
+    /// The DNS cookies of the client and the server, used to detect off-path spoofing (RFC 7873).
+    Cookie {
+        /// The cookie the client sends to the server and expects back in the response.
+        client: [u8; 8],
+        /// The cookie of 8 to 32 octets the server gave the client, `None` until the client has one.
+        server: Option<Vec<u8>>,
+    },
+    /// The number of octets used to pad the message, which are zeros when sent and ignored when received (RFC 7830).
+    Padding(u16),
+    /// Why a response failed or what it is based on (RFC 8914).
+    ExtendedError {
+        /// The INFO-CODE of the error.
+        code: ExtendedErrorCode,
+        /// Text meant for people debugging the error, possibly empty.
+        extra_text: String,
+    },
+    /// An option not known by this implementation.
+    Unknown {
+        /// The option code.
+        code: u16,
+        /// The raw option data.
+        data: Vec<u8>,
+    },
+}
+
+impl EdnsOption {
+    /// Returns the option code.
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => NSID,
+            EdnsOption::ClientSubnet(_) => CLIENT_SUBNET,
+            EdnsOption::Cookie { .. } => COOKIE,
+            EdnsOption::Padding(_) => PADDING,
+            EdnsOption::ExtendedError { .. } => EXTENDED_ERROR,
+            EdnsOption::Unknown { code, .. } => *code,
+        }
+    }
+
+    /// Decodes the wire format data of the option with the given code.
+    ///
+    /// An option whose data is not valid for its code is kept as `Unknown`, so that one malformed
+    /// option does not make the whole message unreadable.
+    pub fn decode(code: u16, data: &[u8]) -> Self {
+        Self::decode_known(code, data).unwrap_or_else(|| EdnsOption::Unknown { code, data: data.to_vec() })
+    }
+
+    /// Decodes the data of the option, `None` if it is not valid for the code.
+    fn decode_known(code: u16, data: &[u8]) -> Option<Self> {
+        let option = match code {
+            NSID => EdnsOption::Nsid(data.to_vec()),
+            CLIENT_SUBNET => EdnsOption::ClientSubnet(ClientSubnet::decode(data)?),
+            COOKIE => {
+                let (client, server) = data.split_first_chunk::<8>()?;
+                let server = match server.len() {
+                    0 => None,
+                    8..=32 => Some(server.to_vec()),
+                    _ => return None,
+                };
+                EdnsOption::Cookie { client: *client, server }
+            }
+            PADDING => EdnsOption::Padding(u16::try_from(data.len()).ok()?),
+            EXTENDED_ERROR => {
+                let (code, extra_text) = data.split_first_chunk::<2>()?;
+                EdnsOption::ExtendedError {
+                    code: ExtendedErrorCode::from(u16::from_be_bytes(*code)),
+                    extra_text: String::from_utf8(extra_text.to_vec()).ok()?,
+                }
+            }
+            code => EdnsOption::Unknown { code, data: data.to_vec() },
+        };
+        Some(option)
+    }
+
+    /// Returns the length of the data of the option in wire format.
+    pub fn data_len(&self) -> usize {
+        match self {
+            EdnsOption::Nsid(id) => id.len(),
+            EdnsOption::ClientSubnet(subnet) => subnet.encoded_len(),
+            EdnsOption::Cookie { server, .. } => 8 + server.as_ref().map_or(0, Vec::len),
+            EdnsOption::Padding(len) => *len as usize,
+            EdnsOption::ExtendedError { extra_text, .. } => 2 + extra_text.len(),
+            EdnsOption::Unknown { data, .. } => data.len(),
+        }
+    }
+
+    /// Encodes the data of the option in wire format.
+    pub fn encode_data(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut data = vec![0; self.data_len()];
+        self.encode_data_into(&mut SliceWriter::new(&mut data))?;
+        Ok(data)
+    }
+
+    /// Writes the data of the option in wire format into `writer`, without allocating.
+    pub fn encode_data_into(&self, writer: &mut SliceWriter) -> Result<(), EncodeError> {
+        match self {
+            EdnsOption::Nsid(id) => writer.write_bytes(id),
+            EdnsOption::ClientSubnet(subnet) => subnet.encode_into(writer),
+            EdnsOption::Cookie { client, server } => {
+                writer.write_bytes(client)?;
+                writer.write_bytes(server.as_deref().unwrap_or_default())
+            }
+            EdnsOption::Padding(len) => {
+                for _ in 0..*len {
+                    writer.write_u8(0)?;
+                }
+                Ok(())
+            }
+            EdnsOption::ExtendedError { code, extra_text } => {
+                writer.write_u16(u16::from(*code))?;
+                writer.write_bytes(extra_text.as_bytes())
+            }
+            EdnsOption::Unknown { data, .. } => writer.write_bytes(data),
+        }
+    }
+}
+
+/// Writes the option like the OPT pseudosection of dig does.
+impl Display for EdnsOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdnsOption::Nsid(id) => {
+                write!(f, "NSID: ")?;
+                write_hex(f, id)?;
+                let text: String = id.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+                write!(f, " (\"{}\")", text)
+            }
+            EdnsOption::ClientSubnet(subnet) => write!(f, "CLIENT-SUBNET: {}", subnet),
+            EdnsOption::Cookie { client, server } => {
+                write!(f, "COOKIE: ")?;
+                write_hex(f, client)?;
+                write_hex(f, server.as_deref().unwrap_or_default())
+            }
+            EdnsOption::Padding(len) => write!(f, "PAD: ({} bytes)", len),
+            EdnsOption::ExtendedError { code, extra_text } if extra_text.is_empty() => write!(f, "EDE: {}", code),
+            EdnsOption::ExtendedError { code, extra_text } => write!(f, "EDE: {}: ({})", code, extra_text),
+            EdnsOption::Unknown { code, data } => {
+                write!(f, "OPT={}: ", code)?;
+                write_hex(f, data)
+            }
+        }
+    }
+}
+
+/// The network of a client as sent in an EDNS Client Subnet option (RFC 7871).
+///
+/// The address only keeps the bits of the source prefix, the others are zero.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix_len: u8,
+    scope_prefix_len: u8,
+}
+
+impl ClientSubnet {
+    /// Builds the subnet of `address` with the given prefix length, clearing the bits of the address
+    /// past the prefix. Returns `None` if the prefix is longer than the address.
+    pub fn new(address: IpAddr, source_prefix_len: u8) -> Option<Self> {
+        if source_prefix_len > max_prefix_len(address) {
+            return None;
+        }
+        Some(Self { address: mask(address, source_prefix_len), source_prefix_len, scope_prefix_len: 0 })
+    }
+
+    /// Returns the network address.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the number of leading bits of the address that identify the client network.
+    pub fn source_prefix_len(&self) -> u8 {
+        self.source_prefix_len
+    }
+
+    /// Returns the number of leading bits of the address the answer is valid for, set by the server
+    /// and always zero in queries.
+    pub fn scope_prefix_len(&self) -> u8 {
+        self.scope_prefix_len
+    }
+
+    /// Decodes the data of the option, `None` if the family is unknown, a prefix is longer than the
+    /// address, the address has more octets than the source prefix needs or bits past it are set.
+    fn decode(data: &[u8]) -> Option<Self> {
+        let (header, address) = data.split_first_chunk::<4>()?;
+        let [family_high, family_low, source_prefix_len, scope_prefix_len] = *header;
+        if address.len() != (source_prefix_len as usize).div_ceil(8) {
+            return None;
+        }
+        let address = match u16::from_be_bytes([family_high, family_low]) {
+            1 => {
+                let mut octets = [0; 4];
+                octets.get_mut(..address.len())?.copy_from_slice(address);
+                IpAddr::from(octets)
+            }
+            2 => {
+                let mut octets = [0; 16];
+                octets.get_mut(..address.len())?.copy_from_slice(address);
+                IpAddr::from(octets)
+            }
+            _ => return None,
+        };
+        let max_len = max_prefix_len(address);
+        if source_prefix_len > max_len || scope_prefix_len > max_len || mask(address, source_prefix_len) != address {
+            return None;
+        }
+        Some(Self { address, source_prefix_len, scope_prefix_len })
+    }
+
+    /// Returns the length of the option data, which has only the octets of the address covered by
+    /// the source prefix.
+    fn encoded_len(&self) -> usize {
+        4 + (self.source_prefix_len as usize).div_ceil(8)
+    }
+
+    /// Writes the option data into `writer`, with only the octets of the address covered by the source prefix.
+    fn encode_into(&self, writer: &mut SliceWriter) -> Result<(), EncodeError> {
+        let len = (self.source_prefix_len as usize).div_ceil(8);
+        let family: u16 = match self.address {
+            IpAddr::V4(_) => 1,
+            IpAddr::V6(_) => 2,
+        };
+        writer.write_u16(family)?;
+        writer.write_u8(self.source_prefix_len)?;
+        writer.write_u8(self.scope_prefix_len)?;
+        match self.address {
+            IpAddr::V4(address) => writer.write_bytes(&address.octets()[..len]),
+            IpAddr::V6(address) => writer.write_bytes(&address.octets()[..len]),
+        }
+    }
+}
+
+/// Returns the length of the address in bits.
+fn max_prefix_len(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Clears the bits of `address` past the first `prefix_len` ones.
+fn mask(address: IpAddr, prefix_len: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::from(Ipv4Addr::from(u32::from(address) & mask))
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::from(Ipv6Addr::from(u128::from(address) & mask))
+        }
+    }
+}
+
+/// Writes the subnet as `address/source/scope`, like dig does.
+impl Display for ClientSubnet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.address, self.source_prefix_len, self.scope_prefix_len)
+    }
+}
+
+/// An error returned when a string is not a subnet like `203.0.113.0/24` or `2001:db8::/48`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubnetParseError(pub String);
+
+impl Display for SubnetParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} is not a subnet like 203.0.113.0/24", self.0)
+    }
+}
+
+impl std::error::Error for SubnetParseError {}
+
+impl FromStr for ClientSubnet {
+    type Err = SubnetParseError;
+
+    /// Parses `address/prefix-length`, a plain address standing for the whole address.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || SubnetParseError(text.to_string());
+        let (address, prefix_len) = match text.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (text, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => max_prefix_len(address),
+        };
+        ClientSubnet::new(address, prefix_len).ok_or_else(invalid)
+    }
+}
+
+/// The EDNS(0) parameters of a message, carried by its OPT pseudo-record (RFC 6891).
+///
+/// The OPT record reuses the fields of a resource record: the class holds the UDP payload size and
+/// the TTL holds the upper eight bits of the response code, the version and the flags.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Edns {
+    /// The largest UDP payload the sender can reassemble, values below 512 are taken as 512.
+    pub udp_payload_size: u16,
+    /// The upper eight bits of the response code, see `Rcode::extended`.
+    pub extended_rcode: u8,
+    /// The EDNS version, only version 0 is defined.
+    pub version: u8,
+    /// Whether the sender can handle DNSSEC records (DO bit, RFC 3225).
+    pub dnssec_ok: bool,
+    /// The options in the order they appear in the record.
+    pub options: Vec<EdnsOption>,
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl Edns {
+    /// Reads the EDNS parameters from an OPT record, or returns `None` if it is not one.
+    pub fn from_record(record: &DNSRecord) -> Option<Self> {
+        let Data::Opt(options) = &record.data else {
+            return None;
+        };
+        Some(Self {
+            udp_payload_size: u16::from(record.class),
+            extended_rcode: (record.ttl >> 24) as u8,
+            version: (record.ttl >> 16) as u8,
+            dnssec_ok: record.ttl & DO_MASK != 0,
+            options: options.clone(),
+        })
+    }
+
+    /// Builds the OPT record carrying these parameters.
+    pub fn to_record(&self) -> DNSRecord {
+        DNSRecord {
+            name: Name::root(),
+            kind: Kind::OPT,
+            class: Class::from(self.udp_payload_size),
+            ttl: self.ttl(),
+            data: Data::Opt(self.options.clone()),
+        }
+    }
+
+    /// Returns the length of the OPT record in wire format.
+    pub fn record_len(&self) -> usize {
+        OPT_RECORD_LEN + self.options.iter().map(|option| 4 + option.data_len()).sum::<usize>()
+    }
+
+    /// Writes the OPT record into `writer`, for `encode_query`.
+    pub fn encode_into(&self, writer: &mut SliceWriter) -> Result<(), EncodeError> {
+        let data_len = self.record_len() - OPT_RECORD_LEN;
+        // The owner is the root name, a single zero octet.
+        writer.write_u8(0)?;
+        writer.write_u16(u16::from(Kind::OPT))?;
+        writer.write_u16(self.udp_payload_size)?;
+        writer.write_u32(self.ttl())?;
+        writer.write_u16(u16::try_from(data_len).map_err(|_| EncodeError::DataTooLong(data_len))?)?;
+        for option in &self.options {
+            let len = option.data_len();
+            writer.write_u16(option.code())?;
+            writer.write_u16(u16::try_from(len).map_err(|_| EncodeError::DataTooLong(len))?)?;
+            option.encode_data_into(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the TTL field of the OPT record: extended response code, version and flags.
+    fn ttl(&self) -> u32 {
+        let dnssec_ok = if self.dnssec_ok { DO_MASK } else { 0 };
+        (self.extended_rcode as u32) << 24 | (self.version as u32) << 16 | dnssec_ok
+    }
+}
+
+/// Writes the parameters like the OPT pseudosection of dig, one option per line.
+impl Display for Edns {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let flags = if self.dnssec_ok { " do" } else { "" };
+        write!(f, "; EDNS: version: {}, flags:{}; udp: {}", self.version, flags, self.udp_payload_size)?;
+        for option in &self.options {
+            write!(f, "\n; {}", option)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_structs::writer::Writer;
+
+    fn options() -> Vec<EdnsOption> {
+        vec![
+            EdnsOption::Nsid(Vec::new()),
+            EdnsOption::ClientSubnet("203.0.113.0/24".parse().unwrap()),
+            EdnsOption::ClientSubnet("2001:db8::/33".parse().unwrap()),
+            EdnsOption::Cookie { client: [1; 8], server: None },
+            EdnsOption::Cookie { client: [1; 8], server: Some(vec![2; 16]) },
+            EdnsOption::Padding(5),
+            EdnsOption::ExtendedError { code: ExtendedErrorCode::from(3), extra_text: "stale".to_string() },
+            EdnsOption::Unknown { code: 65001, data: vec![1, 2, 3] },
+        ]
+    }
+
+    #[test]
+    fn encodes_options_in_place() {
+        let edns = Edns { dnssec_ok: true, options: options(), ..Edns::default() };
+        let mut buffer = vec![0; edns.record_len()];
+        let mut writer = SliceWriter::new(&mut buffer);
+        edns.encode_into(&mut writer).unwrap();
+        assert_eq!(writer.len(), edns.record_len());
+
+        let mut writer = Writer::new();
+        edns.to_record().encode(&mut writer).unwrap();
+        assert_eq!(writer.into_bytes(), buffer);
+    }
+
+    #[test]
+    fn option_data_round_trip() {
+        for option in options() {
+            let data = option.encode_data().unwrap();
+            assert_eq!(data.len(), option.data_len());
+            let decoded = EdnsOption::decode(option.code(), &data);
+            assert_eq!(decoded.to_string(), option.to_string());
+        }
+        assert_eq!(
+            EdnsOption::ClientSubnet("203.0.113.0/24".parse().unwrap()).encode_data().unwrap(),
+            [0, 1, 24, 0, 203, 0, 113]
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_options_as_unknown() {
+        for (code, data) in [(8, &[0, 1, 20, 0, 203, 0, 127][..]), (10, &[1; 5][..]), (15, &[0][..]), (15, &[0, 3, 0xff][..])] {
+            assert!(matches!(EdnsOption::decode(code, data), EdnsOption::Unknown { code: c, data: d } if c == code && d == data));
+        }
+    }
+
+    #[test]
+    fn displays_options_like_dig() {
+        let cases: [(u16, &[u8], &str); 10] = [
+            (3, b"ns1.example", "NSID: 6E73312E6578616D706C65 (\"ns1.example\")"),
+            (3, b"\x00ab\xff", "NSID: 006162FF (\".ab.\")"),
+            (8, &[0, 1, 24, 0, 203, 0, 113], "CLIENT-SUBNET: 203.0.113.0/24/0"),
+            (8, &[0, 2, 33, 48, 0x20, 0x01, 0x0d, 0xb8, 0x80], "CLIENT-SUBNET: 2001:db8:8000::/33/48"),
+            (10, &[1, 2, 3, 4, 5, 6, 7, 8], "COOKIE: 0102030405060708"),
+            (10, b"\xaa\xaa\xaa\xaa\xaa\xaa\xaa\xaa\xbb\xbb\xbb\xbb\xbb\xbb\xbb\xbb", "COOKIE: AAAAAAAAAAAAAAAABBBBBBBBBBBBBBBB"),
+            (12, &[0; 7], "PAD: (7 bytes)"),
+            (15, &[0, 18], "EDE: 18 (Prohibited)"),
+            (15, b"\x00\x03served stale", "EDE: 3 (Stale Answer): (served stale)"),
+            (15, &[0x01, 0x00], "EDE: 256 (Unknown)"),
+        ];
+        for (code, data, text) in cases {
+            assert_eq!(EdnsOption::decode(code, data).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn displays_malformed_options_as_unknown() {
+        let cases: [(u16, &[u8], &str); 5] = [
+            (8, &[0, 1, 20, 0, 203, 0, 127], "OPT=8: 00011400CB007F"),
+            (10, &[1; 5], "OPT=10: 0101010101"),
+            (10, &[1; 7], "OPT=10: 01010101010101"),
+            (15, &[0x00, 0x03, 0xff], "OPT=15: 0003FF"),
+            (65001, &[], "OPT=65001: "),
+        ];
+        for (code, data, text) in cases {
+            let option = EdnsOption::decode(code, data);
+            assert!(matches!(option, EdnsOption::Unknown { .. }), "{code}");
+            assert_eq!(option.to_string(), text);
+        }
+        let edns = Edns { options: vec![EdnsOption::Padding(2), EdnsOption::decode(10, &[1; 5])], ..Edns::default() };
+        assert_eq!(edns.to_string(), "; EDNS: version: 0, flags:; udp: 1232\n; PAD: (2 bytes)\n; OPT=10: 0101010101");
+    }
+}
@@ -1,13 +1,26 @@
-use std::fmt::{Debug, Formatter};
-use std::io::{Read};
-use std::mem::size_of;
+use std::fmt::{Debug, Display, Formatter};
 use strum::IntoEnumIterator;
 use crate::dns_structs::reader::Reader;
-use crate::enums::Flag;
-use crate::transform_result;
+use crate::dns_structs::writer::{EncodeError, SliceWriter, Writer};
+use crate::enums::{Flag, Opcode, Rcode};
+use crate::errors::Error;
 
+const QR_MASK: u16 = 1 << 15;
+const OPCODE_SHIFT: u16 = 11;
+const OPCODE_MASK: u16 = 0b1111 << OPCODE_SHIFT;
+const Z_MASK: u16 = 1 << 6;
+const RCODE_MASK: u16 = 0b1111;
+
+/// The second 16-bit word of the DNS header.
+///
+/// ```text
+///   0  1  2  3  4  5  6  7  8  9 10 11 12 13 14 15
+/// +--+-----------+--+--+--+--+--+--+--+-----------+
+/// |QR|  Opcode   |AA|TC|RD|RA| Z|AD|CD|   RCODE   |
+/// +--+-----------+--+--+--+--+--+--+--+-----------+
+/// ```
 #[repr(transparent)]
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
 pub struct Flags(pub u16);
 
 impl Flags {
@@ -16,11 +29,87 @@ impl Flags {
         let value = flags.iter().fold(0, |acc, flag| acc | *flag as u16);
         Self(value)
     }
+
+    /// Returns whether the given flag is set.
+    pub fn is_set(&self, flag: Flag) -> bool {
+        self.0 & flag as u16 != 0
+    }
+
+    /// Sets or clears the given flag.
+    pub fn set(&mut self, flag: Flag, value: bool) {
+        self.set_bits(flag as u16, value);
+    }
+
+    /// Returns whether the message is a response (QR bit set) rather than a query.
+    pub fn is_response(&self) -> bool {
+        self.0 & QR_MASK != 0
+    }
+
+    /// Marks the message as a response or as a query.
+    pub fn set_response(&mut self, value: bool) {
+        self.set_bits(QR_MASK, value);
+    }
+
+    /// Returns the kind of query in this message.
+    pub fn opcode(&self) -> Opcode {
+        Opcode::from(((self.0 & OPCODE_MASK) >> OPCODE_SHIFT) as u8)
+    }
+
+    /// Sets the kind of query in this message.
+    pub fn set_opcode(&mut self, opcode: Opcode) {
+        self.0 = self.0 & !OPCODE_MASK | ((u8::from(opcode) as u16) << OPCODE_SHIFT) & OPCODE_MASK;
+    }
+
+    /// Returns the reserved Z bit, which must be zero in all queries and responses.
+    pub fn z(&self) -> bool {
+        self.0 & Z_MASK != 0
+    }
+
+    /// Sets the reserved Z bit.
+    pub fn set_z(&mut self, value: bool) {
+        self.set_bits(Z_MASK, value);
+    }
+
+    /// Returns the response code carried in the header.
+    ///
+    /// Only the lower four bits of the response code live in the header, use `DNSPacket::rcode` to
+    /// get the full extended response code.
+    pub fn rcode(&self) -> Rcode {
+        Rcode::from(self.0 & RCODE_MASK)
+    }
+
+    /// Sets the lower four bits of the response code.
+    pub fn set_rcode(&mut self, rcode: Rcode) {
+        self.0 = self.0 & !RCODE_MASK | rcode.header_bits() as u16;
+    }
+
+    fn set_bits(&mut self, mask: u16, value: bool) {
+        if value {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
 }
 
 impl Debug for Flags {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", Flag::iter().filter(|flag| *flag as u16 & self.0 == *flag as u16).collect::<Vec<_>>())
+        f.debug_struct("Flags")
+            .field("qr", &self.is_response())
+            .field("opcode", &self.opcode())
+            .field("flags", &Flag::iter().filter(|flag| self.is_set(*flag)).collect::<Vec<_>>())
+            .field("z", &self.z())
+            .field("rcode", &self.rcode())
+            .finish()
+    }
+}
+
+/// Lists the QR bit and the flags that are set in lowercase, like the `flags:` line of dig.
+impl Display for Flags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let flags = Flag::iter().filter(|flag| self.is_set(*flag)).map(|flag| format!("{:?}", flag).to_lowercase());
+        let names: Vec<String> = self.is_response().then(|| "qr".to_string()).into_iter().chain(flags).collect();
+        write!(f, "{}", names.join(" "))
     }
 }
 
@@ -62,35 +151,79 @@ pub struct DNSHeader {
 }
 
 impl DNSHeader {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0; size_of::<Self>()];
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                self as *const Self as *const u8,
-                bytes.as_mut_ptr(),
-                size_of::<Self>(),
-            );
-        }
-        if cfg!(target_endian = "little") {
-            bytes.chunks_exact_mut(2).for_each(|chunk| chunk.swap(0, 1));
+    /// The size of the header in wire format.
+    pub const LEN: usize = 12;
+
+    /// Returns the identifier used to match up replies to outstanding queries.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Returns the flags word of the header.
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Returns a mutable reference to the flags word of the header.
+    pub fn flags_mut(&mut self) -> &mut Flags {
+        &mut self.flags
+    }
+
+    /// Returns whether the message is a response rather than a query.
+    pub fn is_response(&self) -> bool {
+        self.flags.is_response()
+    }
+
+    /// Returns the kind of query in this message.
+    pub fn opcode(&self) -> Opcode {
+        self.flags.opcode()
+    }
+
+    /// Returns the response code carried in the header (the lower four bits only).
+    pub fn rcode(&self) -> Rcode {
+        self.flags.rcode()
+    }
+
+    /// Returns whether the given flag is set.
+    pub fn is_set(&self, flag: Flag) -> bool {
+        self.flags.is_set(flag)
+    }
+
+    /// Writes the header into `writer`, for `encode_query`.
+    pub fn encode_into(&self, writer: &mut SliceWriter) -> Result<(), EncodeError> {
+        for field in [self.id, self.flags.0, self.num_questions, self.num_answers, self.num_authorities, self.num_additionals] {
+            writer.write_u16(field)?;
         }
-        bytes
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// Writes the header in wire format.
+    pub fn encode(&self, writer: &mut Writer) {
+        writer.write_u16(self.id);
+        writer.write_u16(self.flags.0);
+        writer.write_u16(self.num_questions);
+        writer.write_u16(self.num_answers);
+        writer.write_u16(self.num_authorities);
+        writer.write_u16(self.num_additionals);
     }
 }
 
-impl TryFrom<&mut Reader> for DNSHeader {
-    type Error = String;
+impl TryFrom<&mut Reader<'_>> for DNSHeader {
+    type Error = Error;
 
     fn try_from(reader: &mut Reader) -> Result<Self, Self::Error> {
-        let mut buffer = [0u8; size_of::<Self>()];
-        transform_result!("Cannot read the header from the response", reader.read_exact(&mut buffer))?;
-
-        let id = u16::from_be_bytes([buffer[0], buffer[1]]);
-        let flags = Flags(u16::from_be_bytes([buffer[2], buffer[3]]));
-        let num_questions = u16::from_be_bytes([buffer[4], buffer[5]]);
-        let num_answers = u16::from_be_bytes([buffer[6], buffer[7]]);
-        let num_authorities = u16::from_be_bytes([buffer[8], buffer[9]]);
-        let num_additionals = u16::from_be_bytes([buffer[10], buffer[11]]);
+        let id = reader.next_u16()?;
+        let flags = Flags(reader.next_u16()?);
+        let num_questions = reader.next_u16()?;
+        let num_answers = reader.next_u16()?;
+        let num_authorities = reader.next_u16()?;
+        let num_additionals = reader.next_u16()?;
 
         Ok(Self {
             id,
@@ -101,4 +234,54 @@ impl TryFrom<&mut Reader> for DNSHeader {
             num_additionals,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_keep_every_field_in_its_bits() {
+        let mut flags = Flags::compose(&[Flag::RD, Flag::CD]);
+        assert_eq!(flags.0, 0x0110);
+        flags.set_response(true);
+        flags.set_opcode(Opcode::UPDATE);
+        flags.set_z(true);
+        flags.set_rcode(Rcode::BADCOOKIE);
+        flags.set(Flag::AA, true);
+        assert_eq!(flags.0, 0xad57);
+        assert!(flags.is_response());
+        assert_eq!(flags.opcode(), Opcode::UPDATE);
+        assert!(flags.z());
+        // Only the lower four bits of BADCOOKIE (23) fit in the header.
+        assert_eq!(flags.rcode(), Rcode::YXRRSET);
+        assert!(flags.is_set(Flag::AA) && flags.is_set(Flag::RD) && flags.is_set(Flag::CD));
+        assert!(!flags.is_set(Flag::TC) && !flags.is_set(Flag::RA) && !flags.is_set(Flag::AD));
+        assert_eq!(flags.to_string(), "qr aa rd cd");
+
+        flags.set_response(false);
+        flags.set_opcode(Opcode::Unassigned(15));
+        flags.set_z(false);
+        flags.set_rcode(Rcode::NOERROR);
+        flags.set(Flag::RD, false);
+        assert_eq!(flags.0, 0x7c10);
+        assert_eq!(flags.opcode(), Opcode::Unassigned(15));
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let bytes = [0x12, 0x34, 0x84, 0x83, 0, 1, 0, 2, 0, 3, 0, 4];
+        let header = DNSHeader::try_from(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(header.id(), 0x1234);
+        assert!(header.is_response());
+        assert_eq!(header.opcode(), Opcode::QUERY);
+        assert!(header.is_set(Flag::AA) && header.is_set(Flag::RA) && !header.is_set(Flag::RD));
+        assert_eq!(header.rcode(), Rcode::NXDOMAIN);
+        assert_eq!(
+            (header.num_questions, header.num_answers, header.num_authorities, header.num_additionals),
+            (1, 2, 3, 4)
+        );
+        assert_eq!(header.to_bytes(), bytes);
+        assert!(DNSHeader::try_from(&mut Reader::new(&bytes[..11])).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt::{Display, Formatter, Write};
+use crate::dns_structs::header::DNSHeader;
+use crate::dns_structs::name::{write_label, Name};
+use crate::dns_structs::packet::DNSPacket;
+use crate::dns_structs::question::DNSQuestion;
+use crate::dns_structs::reader::{NameDecoder, Reader};
+use crate::dns_structs::record::{decode_data, Data, DNSRecord};
+use crate::enums::Class;
+use crate::errors::{Error, ResolverResult};
+use crate::Kind;
+
+/// A DNS message parsed lazily over a borrowed buffer.
+///
+/// Only the header is decoded up front. Sections are decoded while they are iterated, names are
+/// exposed as label iterators over the message and record data is kept as a slice until it is
+/// asked for, so inspecting a message does not allocate. Use `DNSPacket::try_from` to get an
+/// owned copy of the whole message.
+#[derive(Copy, Clone, Debug)]
+pub struct DnsMessageRef<'a> {
+    bytes: &'a [u8],
+    header: DNSHeader,
+    /// The offset of the question section, right after the header.
+    questions_start: usize,
+}
+
+impl<'a> DnsMessageRef<'a> {
+    /// Decodes the header of the message in `bytes`.
+    pub fn new(bytes: &'a [u8]) -> ResolverResult<Self> {
+        let mut reader = Reader::new(bytes);
+        let header = DNSHeader::try_from(&mut reader)?;
+        Ok(Self { bytes, header, questions_start: reader.position() })
+    }
+
+    /// Returns the bytes of the whole message.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn header(&self) -> &DNSHeader {
+        &self.header
+    }
+
+    /// Returns an iterator over the question section.
+    pub fn questions(&self) -> Questions<'a> {
+        let mut reader = Reader::new(self.bytes);
+        reader.set_position(self.questions_start);
+        Questions { reader, remaining: self.header.num_questions }
+    }
+
+    /// Returns an iterator over the records of the answer, authority and additional sections in a
+    /// single pass, along with the section of each record.
+    ///
+    /// `answers`, `authorities` and `additionals` skip the sections before theirs every time they are
+    /// called, so this is cheaper when more than one section is needed.
+    pub fn all_records(&self) -> AllRecords<'a> {
+        let counts = [self.header.num_answers, self.header.num_authorities, self.header.num_additionals];
+        AllRecords { records: self.records(0, counts[0]), counts, section: 0 }
+    }
+
+    /// Returns an iterator over the answer section.
+    pub fn answers(&self) -> Records<'a> {
+        self.records(0, self.header.num_answers)
+    }
+
+    /// Returns an iterator over the authority section.
+    pub fn authorities(&self) -> Records<'a> {
+        self.records(self.header.num_answers, self.header.num_authorities)
+    }
+
+    /// Returns an iterator over the additional section.
+    pub fn additionals(&self) -> Records<'a> {
+        self.records(self.header.num_answers.saturating_add(self.header.num_authorities), self.header.num_additionals)
+    }
+
+    /// Returns an iterator over `count` records, starting after the questions and the first `skip` records.
+    fn records(&self, skip: u16, count: u16) -> Records<'a> {
+        let mut questions = self.questions();
+        let mut skip_sections = || {
+            for question in questions.by_ref() {
+                question?;
+            }
+            let mut records = Records { reader: Reader::new(self.bytes), remaining: skip, error: None };
+            records.reader.set_position(questions.reader.position());
+            for record in records.by_ref() {
+                record?;
+            }
+            Ok::<_, Error>(records.reader)
+        };
+        match skip_sections() {
+            Ok(reader) => Records { reader, remaining: count, error: None },
+            Err(err) => Records { reader: Reader::new(self.bytes), remaining: 0, error: Some(err) },
+        }
+    }
+}
+
+impl TryFrom<DnsMessageRef<'_>> for DNSPacket {
+    type Error = Error;
+
+    fn try_from(message: DnsMessageRef<'_>) -> Result<Self, Self::Error> {
+        DNSPacket::try_from(message.bytes)
+    }
+}
+
+/// An iterator over the entries of the question section of a `DnsMessageRef`.
+///
+/// It stops after the first error.
+pub struct Questions<'a> {
+    reader: Reader<'a>,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = ResolverResult<QuestionRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let question = QuestionRef::decode(&mut self.reader);
+        if question.is_err() {
+            self.remaining = 0;
+        }
+        Some(question)
+    }
+}
+
+/// An iterator over the records of a section of a `DnsMessageRef`.
+///
+/// It stops after the first error.
+pub struct Records<'a> {
+    reader: Reader<'a>,
+    remaining: u16,
+    /// An error found while looking for the start of the section.
+    error: Option<Error>,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = ResolverResult<RecordRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let record = RecordRef::decode(&mut self.reader);
+        if record.is_err() {
+            self.remaining = 0;
+        }
+        Some(record)
+    }
+}
+
+/// A section of a message holding resource records.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Section {
+    /// The records answering the question.
+    Answer,
+    /// The records pointing toward an authoritative name server.
+    Authority,
+    /// The records that relate to the query but do not strictly answer it.
+    Additional,
+}
+
+const SECTIONS: [Section; 3] = [Section::Answer, Section::Authority, Section::Additional];
+
+/// An iterator over the records of every section of a `DnsMessageRef`, see `DnsMessageRef::all_records`.
+///
+/// It stops after the first error.
+pub struct AllRecords<'a> {
+    records: Records<'a>,
+    /// The number of records of each section, in the order of `SECTIONS`.
+    counts: [u16; 3],
+    /// The index in `SECTIONS` of the section being read.
+    section: usize,
+}
+
+impl<'a> Iterator for AllRecords<'a> {
+    type Item = ResolverResult<(Section, RecordRef<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let section = *SECTIONS.get(self.section)?;
+            match self.records.next() {
+                Some(Ok(record)) => return Some(Ok((section, record))),
+                Some(Err(err)) => {
+                    self.section = SECTIONS.len();
+                    return Some(Err(err));
+                }
+                None => {
+                    self.section += 1;
+                    self.records.remaining = self.counts.get(self.section).copied().unwrap_or(0);
+                }
+            }
+        }
+    }
+}
+
+/// A question borrowed from a `DnsMessageRef`.
+#[derive(Copy, Clone, Debug)]
+pub struct QuestionRef<'a> {
+    pub name: NameRef<'a>,
+    pub kind: Kind,
+    pub class: Class,
+}
+
+impl<'a> QuestionRef<'a> {
+    fn decode(reader: &mut Reader<'a>) -> ResolverResult<Self> {
+        let name = NameRef::decode(reader)?;
+        Ok(Self { name, kind: Kind::from(reader.next_u16()?), class: Class::from(reader.next_u16()?) })
+    }
+
+    /// Returns an owned copy of the question.
+    pub fn to_question(&self) -> ResolverResult<DNSQuestion> {
+        Ok(DNSQuestion { name: self.name.to_name()?, kind: self.kind, class: self.class })
+    }
+}
+
+/// A resource record borrowed from a `DnsMessageRef`.
+#[derive(Copy, Clone, Debug)]
+pub struct RecordRef<'a> {
+    pub name: NameRef<'a>,
+    pub kind: Kind,
+    pub class: Class,
+    pub ttl: u32,
+    /// The raw RDATA of the record. Names inside it may be compressed, use `data` to decode them.
+    pub rdata: &'a [u8],
+    message: &'a [u8],
+    rdata_offset: usize,
+}
+
+impl<'a> RecordRef<'a> {
+    fn decode(reader: &mut Reader<'a>) -> ResolverResult<Self> {
+        let name = NameRef::decode(reader)?;
+        let kind = Kind::from(reader.next_u16()?);
+        let class = Class::from(reader.next_u16()?);
+        let ttl = reader.next_u32()?;
+        let data_len = reader.next_u16()?;
+        let rdata_offset = reader.position();
+        let rdata = reader.next_slice(data_len as usize)?;
+        Ok(Self { name, kind, class, ttl, rdata, message: reader.bytes(), rdata_offset })
+    }
+
+    /// Decodes the data of the record.
+    pub fn data(&self) -> ResolverResult<Data> {
+        let mut reader = Reader::new(self.message);
+        reader.set_position(self.rdata_offset);
+        Ok(reader.with_limit(self.rdata.len(), |reader| decode_data(reader, self.kind))?)
+    }
+
+    /// Returns an owned copy of the record.
+    pub fn to_record(&self) -> ResolverResult<DNSRecord> {
+        Ok(DNSRecord { name: self.name.to_name()?, kind: self.kind, class: self.class, ttl: self.ttl, data: self.data()? })
+    }
+}
+
+/// A domain name borrowed from a `DnsMessageRef`, possibly compressed.
+///
+/// The name has been checked when the question or record holding it was decoded, so iterating its
+/// labels never fails.
+#[derive(Copy, Clone, Debug)]
+pub struct NameRef<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    /// Checks the name at the position of `reader` and skips it.
+    fn decode(reader: &mut Reader<'a>) -> ResolverResult<Self> {
+        let offset = reader.position();
+        reader.skip_name()?;
+        Ok(Self { message: reader.bytes(), offset })
+    }
+
+    /// Returns the offset of the name in the message.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns an iterator over the labels of the name, following compression pointers, without the root label.
+    pub fn labels(&self) -> Labels<'a> {
+        Labels { message: self.message, position: self.offset, sequence_start: self.offset }
+    }
+
+    /// Returns whether the name is the same as `other` including the case of their letters, unlike `==`.
+    pub fn eq_case_sensitive(&self, other: &Name) -> bool {
+        let mut labels = other.labels();
+        self.labels().all(|label| labels.next() == Some(label)) && labels.next().is_none()
+    }
+
+    /// Returns an owned copy of the name.
+    pub fn to_name(&self) -> ResolverResult<Name> {
+        let mut reader = Reader::new(self.message);
+        reader.set_position(self.offset);
+        Ok(reader.decode_name()?)
+    }
+}
+
+impl PartialEq<Name> for NameRef<'_> {
+    /// Compares the labels of both names ignoring ASCII case, like `Name` does.
+    fn eq(&self, other: &Name) -> bool {
+        let mut labels = other.labels();
+        self.labels().all(|label| labels.next().is_some_and(|other| other.eq_ignore_ascii_case(label)))
+            && labels.next().is_none()
+    }
+}
+
+impl Display for NameRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut labels = self.labels().peekable();
+        if labels.peek().is_none() {
+            return f.write_char('.');
+        }
+        for label in labels {
+            write_label(f, label)?;
+            f.write_char('.')?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the labels of a `NameRef`.
+pub struct Labels<'a> {
+    message: &'a [u8],
+    position: usize,
+    sequence_start: usize,
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let length = *self.message.get(self.position)?;
+            match length >> 6 {
+                0b00 if length == 0 => return None,
+                0b00 => {
+                    let start = self.position + 1;
+                    self.position = start + length as usize;
+                    return self.message.get(start..self.position);
+                }
+                0b11 => {
+                    let low = *self.message.get(self.position + 1)?;
+                    let target = ((length & 0b0011_1111) as usize) << 8 | low as usize;
+                    // Same rule as the decoder: only jump backwards, out of the current label sequence.
+                    if target >= self.sequence_start {
+                        return None;
+                    }
+                    self.sequence_start = target;
+                    self.position = target;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_structs::header::Flags;
+
+    /// A response with compressed names in every section and inside the MX data.
+    fn message() -> Vec<u8> {
+        let record = |text: &str| text.parse::<DNSRecord>().unwrap();
+        DNSPacket {
+            header: DNSHeader { id: 7, flags: Flags(0x8180), ..DNSHeader::default() },
+            questions: vec![DNSQuestion { name: "Example.com.".parse().unwrap(), kind: Kind::MX, class: Class::IN }],
+            answers: vec![record("example.com. 300 IN MX 10 mail.example.com.")],
+            authorities: vec![
+                record("example.com. 300 IN NS ns1.example.com."),
+                record("example.com. 300 IN NS ns2.example.com."),
+            ],
+            additionals: vec![record("mail.example.com. 300 IN A 192.0.2.25")],
+        }
+        .to_bytes()
+        .unwrap()
+    }
+
+    /// Returns the records of a section in master-file format.
+    fn section(records: Records) -> Vec<String> {
+        records.map(|record| record.unwrap().to_record().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn iterates_every_section() {
+        let bytes = message();
+        let message = DnsMessageRef::new(&bytes).unwrap();
+        assert_eq!(message.header().id(), 7);
+        let questions: Vec<_> = message.questions().map(Result::unwrap).collect();
+        assert_eq!(questions.len(), 1);
+        assert_eq!((questions[0].name.to_string().as_str(), questions[0].kind), ("Example.com.", Kind::MX));
+
+        // Every name points to the question name, so they all take its case.
+        assert_eq!(section(message.answers()), ["Example.com.\t300\tIN\tMX\t10 mail.Example.com."]);
+        assert_eq!(
+            section(message.authorities()),
+            ["Example.com.\t300\tIN\tNS\tns1.Example.com.", "Example.com.\t300\tIN\tNS\tns2.Example.com."]
+        );
+        assert_eq!(section(message.additionals()), ["mail.Example.com.\t300\tIN\tA\t192.0.2.25"]);
+
+        let all: Vec<_> = message.all_records()
+            .map(|record| record.map(|(section, record)| (section, record.kind)).unwrap())
+            .collect();
+        let expected = [
+            (Section::Answer, Kind::MX), (Section::Authority, Kind::NS), (Section::Authority, Kind::NS), (Section::Additional, Kind::A),
+        ];
+        assert_eq!(all, expected);
+    }
+
+    #[test]
+    fn decodes_compressed_names_inside_rdata() {
+        let bytes = message();
+        let message = DnsMessageRef::new(&bytes).unwrap();
+        let answer = message.answers().next().unwrap().unwrap();
+        // The exchange is `mail` followed by a pointer to the question name.
+        assert_eq!(answer.rdata, b"\x00\x0a\x04mail\xc0\x0c");
+        let Data::Mx { exchange, .. } = answer.data().unwrap() else { panic!("the answer should be an MX record") };
+        assert_eq!(exchange.to_string(), "mail.Example.com.");
+    }
+
+    #[test]
+    fn malformed_sections_yield_one_error() {
+        let mut bytes = message();
+        // Cut the message in the middle of the second authority record.
+        let second_ns = DnsMessageRef::new(&bytes).unwrap().authorities().nth(1).unwrap().unwrap().name.offset();
+        bytes.truncate(second_ns + 4);
+        let message = DnsMessageRef::new(&bytes).unwrap();
+        assert_eq!(message.answers().filter(Result::is_ok).count(), 1);
+        let mut authorities = message.authorities();
+        assert!(authorities.next().unwrap().is_ok());
+        assert!(authorities.next().unwrap().is_err());
+        assert!(authorities.next().is_none());
+        let mut additionals = message.additionals();
+        assert!(additionals.next().unwrap().is_err());
+        assert!(additionals.next().is_none());
+        let results: Vec<_> = message.all_records().map(|record| record.is_ok()).collect();
+        assert_eq!(results, [true, true, false]);
+        assert!(DnsMessageRef::new(&bytes[..11]).is_err());
+    }
+
+    #[test]
+    fn names_compare_with_or_without_case() {
+        let bytes = message();
+        let message = DnsMessageRef::new(&bytes).unwrap();
+        let question = message.questions().next().unwrap().unwrap();
+        let lower: Name = "example.com.".parse().unwrap();
+        let exact: Name = "Example.com.".parse().unwrap();
+        assert!(question.name == lower && question.name == exact);
+        assert!(question.name.eq_case_sensitive(&exact));
+        assert!(!question.name.eq_case_sensitive(&lower));
+        assert!(question.name != "example.org.".parse::<Name>().unwrap());
+        assert!(question.name != "www.example.com.".parse::<Name>().unwrap());
+        assert!(!question.name.eq_case_sensitive(&"com.".parse().unwrap()));
+    }
+}
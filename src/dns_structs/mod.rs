@@ -1,5 +1,12 @@
+pub mod edns;
 pub mod header;
+pub mod name;
 pub mod question;
 pub mod record;
 pub mod reader;
-pub mod packet;
\ No newline at end of file
+pub mod packet;
+pub mod message_ref;
+pub mod svcb;
+pub mod writer;
+pub mod zone;
+pub(crate) mod text;
\ No newline at end of file
@@ -0,0 +1,421 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use crate::dns_structs::reader::{MAX_LABEL_LEN, MAX_NAME_LEN};
+
+/// An error found while building a `Name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameParseError {
+    /// The name has an empty label, like `a..b` or `.a`.
+    EmptyLabel,
+    /// The name is not a valid internationalized domain name; holds the input.
+    InvalidIdn(String),
+    /// A backslash is not followed by a character or by exactly three digits of a value up to 255.
+    InvalidEscape,
+    /// A label is longer than 63 octets; holds its length.
+    LabelTooLong(usize),
+    /// The name is longer than 255 octets in wire format; holds its length.
+    NameTooLong(usize),
+}
+
+impl Display for NameParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameParseError::EmptyLabel => write!(f, "empty label"),
+            NameParseError::InvalidEscape => write!(f, "invalid escape sequence"),
+            NameParseError::InvalidIdn(name) => write!(f, "{} is not a valid internationalized domain name", name),
+            NameParseError::LabelTooLong(len) => write!(f, "label of {} bytes exceeds 63 bytes", len),
+            NameParseError::NameTooLong(len) => write!(f, "name of {} bytes exceeds 255 bytes", len),
+        }
+    }
+}
+
+impl std::error::Error for NameParseError {}
+
+/// A fully qualified domain name.
+///
+/// The name is kept in uncompressed wire format, so it is valid by construction: no empty labels,
+/// labels of at most 63 octets and at most 255 octets in total. Comparisons ignore ASCII case and
+/// `Ord` follows the canonical ordering of [RFC 4034 section 6.1](https://www.rfc-editor.org/rfc/rfc4034#section-6.1).
+///
+/// Labels are arbitrary octets. Names are written in master-file format (RFC 1035 section 5.1) with a
+/// trailing dot, the root being `.`: dots and backslashes inside labels are written as `\.` and `\\`
+/// and octets that are not printable ASCII as `\DDD`. When parsing, the trailing dot is optional
+/// since every name is taken as absolute.
+///
+/// `from_idn` converts internationalized names typed by users to the A-labels (`xn--`) used on the
+/// wire and the alternate flag (`{:#}`) shows them back in Unicode.
+#[derive(Clone)]
+pub struct Name {
+    /// Length prefixed labels ending with the zero length root label.
+    wire: Vec<u8>,
+}
+
+impl Name {
+    /// Returns the root name.
+    pub fn root() -> Self {
+        Self { wire: vec![0] }
+    }
+
+    /// Builds a name from its labels, from the leftmost one up to, but not including, the root.
+    pub fn from_labels<I, L>(labels: I) -> Result<Self, NameParseError>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut wire = Vec::new();
+        for label in labels {
+            let label = label.as_ref();
+            if label.is_empty() {
+                return Err(NameParseError::EmptyLabel);
+            }
+            if label.len() > MAX_LABEL_LEN {
+                return Err(NameParseError::LabelTooLong(label.len()));
+            }
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+        if wire.len() > MAX_NAME_LEN {
+            return Err(NameParseError::NameTooLong(wire.len()));
+        }
+        Ok(Self { wire })
+    }
+
+    /// Parses a name typed by a user, which may be an internationalized domain name.
+    ///
+    /// Names with non-ASCII characters or `xn--` labels go through UTS #46 processing and are
+    /// converted to A-labels, so `bücher.de` becomes `xn--bcher-kva.de.`. Other names are parsed
+    /// like `from_str` does, which keeps names like `_dmarc.example.com` that IDNA would reject.
+    pub fn from_idn(name: &str) -> Result<Self, NameParseError> {
+        let is_idn = !name.is_ascii()
+            || name.split('.').any(|label| label.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("xn--")));
+        if !is_idn {
+            return name.parse();
+        }
+        let ascii = idna::domain_to_ascii(name).map_err(|_| NameParseError::InvalidIdn(name.to_string()))?;
+        ascii.parse()
+    }
+
+    /// Wraps a name already checked by the decoder.
+    pub(crate) fn from_wire(wire: Vec<u8>) -> Self {
+        Self { wire }
+    }
+
+    /// Returns the name in uncompressed wire format.
+    pub fn wire(&self) -> &[u8] {
+        &self.wire
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.wire.len() == 1
+    }
+
+    /// Returns the labels of the name from left to right, without the root label.
+    pub fn labels(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.label_offsets().map(|offset| &self.wire[offset + 1..offset + 1 + self.wire[offset] as usize])
+    }
+
+    /// Returns the number of labels, without the root label.
+    pub fn num_labels(&self) -> usize {
+        self.label_offsets().count()
+    }
+
+    /// Returns the name without its leftmost label, or `None` for the root.
+    pub fn parent(&self) -> Option<Name> {
+        let first = *self.wire.first()? as usize;
+        if first == 0 {
+            return None;
+        }
+        Some(Self { wire: self.wire[first + 1..].to_vec() })
+    }
+
+    /// Returns this name followed by the labels of `suffix`, as when completing a relative name with an origin.
+    pub fn append(&self, suffix: &Name) -> Result<Name, NameParseError> {
+        Self::from_labels(self.labels().chain(suffix.labels()))
+    }
+
+    /// Returns whether this name is `other` or lies below it.
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        let Some(skip) = self.num_labels().checked_sub(other.num_labels()) else {
+            return false;
+        };
+        let offset = self.label_offsets().nth(skip).unwrap_or(self.wire.len() - 1);
+        self.wire[offset..].eq_ignore_ascii_case(&other.wire)
+    }
+
+    /// Returns the name with its ASCII letters in lowercase, as used by the DNSSEC canonical form.
+    pub fn to_lowercase(&self) -> Name {
+        Self { wire: self.wire.to_ascii_lowercase() }
+    }
+
+    /// Returns the name with the case of every ASCII letter chosen at random, so that a response
+    /// echoing the exact same case is hard to forge (DNS 0x20, draft-vixie-dnsext-dns0x20).
+    pub fn randomize_case(&self) -> Name {
+        let wire = self.wire.iter()
+            .map(|&byte| if rand::random() { byte.to_ascii_uppercase() } else { byte.to_ascii_lowercase() })
+            .collect();
+        Self { wire }
+    }
+
+    /// Returns whether both names are the same including the case of their letters, unlike `==`.
+    pub fn eq_case_sensitive(&self, other: &Name) -> bool {
+        self.wire == other.wire
+    }
+
+    /// Returns the offsets in `wire` of the length octets of every label but the root.
+    pub(crate) fn label_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let len = self.wire[offset] as usize;
+            if len == 0 {
+                return None;
+            }
+            let label = offset;
+            offset += len + 1;
+            Some(label)
+        })
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl FromStr for Name {
+    type Err = NameParseError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name.is_empty() || name == "." {
+            return Ok(Self::root());
+        }
+        let bytes = name.as_bytes();
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        // An unescaped trailing dot only marks the name as absolute.
+        let mut trailing_dot = false;
+        let mut i = 0;
+        while i < bytes.len() {
+            trailing_dot = bytes[i] == b'.';
+            match bytes[i] {
+                b'\\' => {
+                    let digits = bytes.get(i + 1..i + 4).filter(|digits| digits.iter().all(u8::is_ascii_digit));
+                    if let Some(digits) = digits {
+                        let value = digits.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as u16);
+                        label.push(u8::try_from(value).map_err(|_| NameParseError::InvalidEscape)?);
+                        i += 4;
+                    } else {
+                        match bytes.get(i + 1) {
+                            Some(byte) if !byte.is_ascii_digit() => label.push(*byte),
+                            _ => return Err(NameParseError::InvalidEscape),
+                        }
+                        i += 2;
+                    }
+                }
+                b'.' => {
+                    labels.push(std::mem::take(&mut label));
+                    i += 1;
+                }
+                byte => {
+                    label.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        if !trailing_dot {
+            labels.push(label);
+        }
+        Self::from_labels(labels)
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_root() {
+            return f.write_char('.');
+        }
+        for label in self.labels() {
+            write_label(f, label)?;
+            f.write_char('.')?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a label in master-file format, escaping the characters that are special in names and
+/// zone files and writing the octets that are not printable ASCII as `\DDD`.
+///
+/// With the alternate flag, valid A-labels are written in Unicode instead.
+pub(crate) fn write_label(f: &mut Formatter<'_>, label: &[u8]) -> std::fmt::Result {
+    if f.alternate() && label.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case(b"xn--")) {
+        if let Ok(ascii) = std::str::from_utf8(label) {
+            if let (unicode, Ok(())) = idna::domain_to_unicode(ascii) {
+                return f.write_str(&unicode);
+            }
+        }
+    }
+    for &byte in label {
+        match byte {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => write!(f, "\\{}", byte as char)?,
+            0x21..=0x7e => f.write_char(byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+impl Debug for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Name(\"{}\")", self)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        // Length octets are below 64, so they are never changed by the ASCII case folding.
+        self.wire.eq_ignore_ascii_case(&other.wire)
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for byte in &self.wire {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    /// Sorts names by their labels from right to left, comparing labels as lowercase octet strings
+    /// where a missing label sorts first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ours: Vec<&[u8]> = self.labels().collect();
+        let theirs: Vec<&[u8]> = other.labels().collect();
+        ours.iter().rev().zip(theirs.iter().rev())
+            .map(|(a, b)| a.iter().map(u8::to_ascii_lowercase).cmp(b.iter().map(u8::to_ascii_lowercase)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| ours.len().cmp(&theirs.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap_or_else(|error| panic!("{text:?} should parse: {error}"))
+    }
+
+    #[test]
+    fn enforces_label_and_name_lengths() {
+        assert_eq!("a..b".parse::<Name>().unwrap_err(), NameParseError::EmptyLabel);
+        assert_eq!(".a".parse::<Name>().unwrap_err(), NameParseError::EmptyLabel);
+        assert_eq!(Name::from_labels([&b"a"[..], b""]).unwrap_err(), NameParseError::EmptyLabel);
+        assert_eq!(name(&"a".repeat(63)).wire().len(), 65);
+        assert_eq!("a".repeat(64).parse::<Name>().unwrap_err(), NameParseError::LabelTooLong(64));
+        // Four labels of 63 octets take 4 * 64 + 1 = 257 octets on the wire.
+        let label = "a".repeat(63);
+        let longest = [label.as_str(), &label, &label, &"a".repeat(61)].join(".");
+        assert_eq!(name(&longest).wire().len(), 255);
+        assert_eq!([longest.as_str(), "a"].join("").parse::<Name>().unwrap_err(), NameParseError::NameTooLong(256));
+        let too_long = [label.as_str(), &label, &label, &label].join(".");
+        assert_eq!(too_long.parse::<Name>().unwrap_err(), NameParseError::NameTooLong(257));
+    }
+
+    #[test]
+    fn compares_and_hashes_ignoring_case() {
+        assert_eq!(name("WWW.Example.COM"), name("www.example.com."));
+        assert_ne!(name("www.example.com"), name("www.example.net"));
+        let names: HashSet<Name> = ["example.com", "EXAMPLE.com.", "Example.Com"].into_iter().map(name).collect();
+        assert_eq!(names.len(), 1);
+        assert!(name("Example.com").eq_case_sensitive(&name("Example.com")));
+        assert!(!name("Example.com").eq_case_sensitive(&name("example.com")));
+        let randomized = name("www.example.com").randomize_case();
+        assert_eq!(randomized, name("www.example.com"));
+        assert_eq!(randomized.to_lowercase().to_string(), "www.example.com.");
+    }
+
+    #[test]
+    fn sorts_in_canonical_order() {
+        // The example of RFC 4034 section 6.1, already sorted.
+        let sorted = [
+            "example", "a.example", "yljkjljk.a.example", "Z.a.example", "zABC.a.EXAMPLE",
+            "z.example", "\\001.z.example", "*.z.example", "\\200.z.example",
+        ];
+        let mut names: Vec<Name> = sorted.iter().rev().map(|text| name(text)).collect();
+        names.sort();
+        let names: Vec<String> = names.iter().map(Name::to_string).collect();
+        let expected: Vec<String> = sorted.iter().map(|text| name(text).to_string()).collect();
+        assert_eq!(names, expected);
+        assert!(Name::root() < name("com"));
+    }
+
+    #[test]
+    fn walks_the_hierarchy() {
+        let www = name("www.example.com");
+        assert_eq!(www.parent(), Some(name("example.com")));
+        assert_eq!(name("com").parent(), Some(Name::root()));
+        assert_eq!(Name::root().parent(), None);
+        assert_eq!(www.num_labels(), 3);
+        assert_eq!(name("www").append(&name("Example.com")).unwrap(), www);
+        assert_eq!(Name::root().append(&www).unwrap(), www);
+        let label = "a".repeat(63);
+        let long = name(&[label.as_str(), &label, &label].join("."));
+        assert_eq!(long.append(&long).unwrap_err(), NameParseError::NameTooLong(385));
+        assert!(www.is_subdomain_of(&name("EXAMPLE.com")));
+        assert!(www.is_subdomain_of(&www));
+        assert!(www.is_subdomain_of(&Name::root()));
+        assert!(!www.is_subdomain_of(&name("ample.com")));
+        assert!(!name("example.com").is_subdomain_of(&www));
+    }
+
+    #[test]
+    fn escapes_binary_labels() {
+        let labels: [&[u8]; 6] = [b"a.b", b"back\\slash", b"\"quoted\"", b"with space", b"\xff\x00\xc3", b"@$;()"];
+        let name = Name::from_labels(labels).unwrap();
+        let text = name.to_string();
+        assert_eq!(
+            text,
+            r#"a\.b.back\\slash.\"quoted\".with\032space.\255\000\195.\@\$\;\(\)."#
+        );
+        assert_eq!(text.parse::<Name>().unwrap().wire(), name.wire());
+        assert_eq!(name.labels().collect::<Vec<_>>(), labels);
+        // Any character may be escaped, not only the special ones.
+        assert_eq!(self::name(r"\a\098c.\.").labels().collect::<Vec<_>>(), [&b"abc"[..], b"."]);
+    }
+
+    #[test]
+    fn rejects_invalid_escapes() {
+        for text in [r"a\256", r"a\999.com", r"com\", r"a\1", r"a\12.com"] {
+            assert_eq!(text.parse::<Name>().unwrap_err(), NameParseError::InvalidEscape, "{text}");
+        }
+        assert_eq!(self::name(r"a\255").labels().next(), Some(&b"a\xff"[..]));
+        assert_eq!(self::name(r"a\\").labels().next(), Some(&b"a\\"[..]));
+    }
+
+    #[test]
+    fn converts_internationalized_names() {
+        let name = Name::from_idn("bücher.de").unwrap();
+        assert_eq!(name.to_string(), "xn--bcher-kva.de.");
+        assert_eq!(format!("{name:#}"), "bücher.de.");
+        assert_eq!(Name::from_idn("BÜCHER.de.").unwrap(), name);
+        assert_eq!(Name::from_idn("xn--bcher-kva.de").unwrap(), name);
+        // ASCII names skip IDNA, which would reject underscores.
+        assert_eq!(Name::from_idn("_dmarc.example.com").unwrap(), self::name("_dmarc.example.com"));
+        assert!(matches!(Name::from_idn("xn--a.de"), Err(NameParseError::InvalidIdn(_))));
+        // Labels that are not valid A-labels stay as they are.
+        assert_eq!(format!("{:#}", self::name("xn--a.de")), "xn--a.de.");
+    }
+}
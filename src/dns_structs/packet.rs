@@ -1,8 +1,14 @@
+use std::fmt::{Display, Formatter};
 use std::net::Ipv4Addr;
+use crate::dns_structs::edns::Edns;
 use crate::dns_structs::header::DNSHeader;
+use crate::dns_structs::name::Name;
 use crate::dns_structs::question::DNSQuestion;
 use crate::dns_structs::reader::Reader;
 use crate::dns_structs::record::{Data, DNSRecord};
+use crate::dns_structs::writer::{EncodeError, Writer};
+use crate::errors::{Error, ResolverResult};
+use crate::enums::Rcode;
 use crate::Kind;
 
 #[derive(Debug)]
@@ -15,10 +21,58 @@ pub struct DNSPacket {
 }
 
 impl DNSPacket {
+    /// Returns the response code of the packet, including the upper bits carried by the OPT record if there is one.
+    pub fn rcode(&self) -> Rcode {
+        match self.opt_record() {
+            Some(opt) => Rcode::extended(self.header.rcode().header_bits(), (opt.ttl >> 24) as u8),
+            None => self.header.rcode(),
+        }
+    }
+
+    /// Returns the EDNS parameters of the packet, or `None` if it has no OPT record.
+    pub fn edns(&self) -> Option<Edns> {
+        self.opt_record().and_then(Edns::from_record)
+    }
+
+    /// Replaces the OPT record of the packet, or removes it when `edns` is `None`.
+    pub fn set_edns(&mut self, edns: Option<Edns>) {
+        self.additionals.retain(|record| record.kind != Kind::OPT);
+        self.additionals.extend(edns.map(|edns| edns.to_record()));
+    }
+
+    /// Returns the OPT record, which lives in the additional section.
+    fn opt_record(&self) -> Option<&DNSRecord> {
+        self.additionals.iter().find(|record| record.kind == Kind::OPT)
+    }
+
+    /// Serializes the packet in wire format applying name compression.
+    ///
+    /// The section counts of the header are taken from the lengths of the sections.
+    pub fn to_bytes(&self) -> ResolverResult<Vec<u8>> {
+        let count = |section: usize| u16::try_from(section).map_err(|_| EncodeError::TooManyRecords(section));
+        let header = DNSHeader {
+            num_questions: count(self.questions.len())?,
+            num_answers: count(self.answers.len())?,
+            num_authorities: count(self.authorities.len())?,
+            num_additionals: count(self.additionals.len())?,
+            ..self.header
+        };
+        let mut writer = Writer::new();
+        header.encode(&mut writer);
+        for question in &self.questions {
+            question.encode(&mut writer);
+        }
+        for record in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            record.encode(&mut writer)?;
+        }
+        Ok(writer.into_bytes())
+    }
+
+    /// Returns the data of the answers that match the given QTYPE (see `Kind::matches`).
     pub fn get_answers(&self, record_type: Kind) -> Vec<Data> {
         self.answers
             .iter()
-            .filter(|answer| answer.kind == record_type || record_type == Kind::ANY)
+            .filter(|answer| record_type.matches(answer.kind))
             .map(|answer| answer.data.clone() )
             .collect()
     }
@@ -26,19 +80,17 @@ impl DNSPacket {
     pub fn get_name_server_ip(&self) -> Option<Ipv4Addr> {
         self.additionals
             .iter()
-            .filter(|answer| answer.kind == Kind::A)
-            .next()
+            .find(|answer| answer.kind == Kind::A)
             .map(|answer| match answer.data {
                 Data::IPv4(ip) => ip,
                 _ => unreachable!()
             })
     }
 
-    pub fn get_name_server(&self) -> Option<String> {
+    pub fn get_name_server(&self) -> Option<Name> {
         self.authorities
             .iter()
-            .filter(|answer| answer.kind == Kind::NS)
-            .next()
+            .find(|answer| answer.kind == Kind::NS)
             .map(|answer| match &answer.data {
                 Data::Host(host) => host.clone(),
                 _ => unreachable!()
@@ -46,10 +98,72 @@ impl DNSPacket {
     }
 }
 
+/// Writes the packet the way dig does: a summary of the header, the EDNS parameters and the
+/// non-empty sections. The OPT record is only shown in the OPT pseudosection.
+///
+/// ```text
+/// ;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4660
+/// ;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1
+///
+/// ;; OPT PSEUDOSECTION:
+/// ; EDNS: version: 0, flags:; udp: 1232
+///
+/// ;; QUESTION SECTION:
+/// ;example.com.        IN  A
+///
+/// ;; ANSWER SECTION:
+/// example.com.    300 IN  A   93.184.216.34
+/// ```
+impl Display for DNSPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, ";; ->>HEADER<<- opcode: {}, status: {}, id: {}", self.header.opcode(), self.rcode(), self.header.id())?;
+        write!(
+            f,
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            self.header.flags(),
+            self.questions.len(),
+            self.answers.len(),
+            self.authorities.len(),
+            self.additionals.len(),
+        )?;
+        if let Some(edns) = self.edns() {
+            write!(f, "\n\n;; OPT PSEUDOSECTION:\n{}", edns)?;
+        }
+        if !self.questions.is_empty() {
+            write!(f, "\n\n;; QUESTION SECTION:")?;
+            for question in &self.questions {
+                write!(f, "\n;")?;
+                question.fmt(f)?;
+            }
+        }
+        let sections: [(&str, Vec<&DNSRecord>); 3] = [
+            ("ANSWER", self.answers.iter().collect()),
+            ("AUTHORITY", self.authorities.iter().collect()),
+            ("ADDITIONAL", self.additionals.iter().filter(|record| record.kind != Kind::OPT).collect()),
+        ];
+        for (title, records) in sections.into_iter().filter(|(_, records)| !records.is_empty()) {
+            write!(f, "\n\n;; {} SECTION:", title)?;
+            for record in records {
+                writeln!(f)?;
+                record.fmt(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl TryFrom<Vec<u8>> for DNSPacket {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        DNSPacket::try_from(value.as_slice())
+    }
+}
+
+impl TryFrom<&[u8]> for DNSPacket {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut reader = Reader::new(value);
         let header = DNSHeader::try_from(&mut reader)?;
         Ok(Self {
@@ -68,4 +182,188 @@ impl TryFrom<Vec<u8>> for DNSPacket {
                 .collect::<Result<Vec<_>, _>>()?,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_structs::header::Flags;
+    use crate::enums::Class;
+    use crate::dns_structs::edns::EdnsOption;
+
+    fn record(text: &str) -> DNSRecord {
+        text.parse().unwrap()
+    }
+
+    /// A response for `example.com. A` with a record in every section.
+    fn response() -> DNSPacket {
+        DNSPacket {
+            header: DNSHeader { id: 0x1234, flags: Flags(0x8180), ..DNSHeader::default() },
+            questions: vec![DNSQuestion { name: "example.com.".parse().unwrap(), kind: Kind::A, class: Class::IN }],
+            answers: vec![
+                record("example.com. 300 IN A 192.0.2.1"),
+                record("_sip._tcp.example.com. 300 IN SRV 10 20 5060 sip.example.com."),
+            ],
+            authorities: vec![record("example.com. 300 IN NS ns1.example.com.")],
+            additionals: vec![record("ns1.example.com. 300 IN A 192.0.2.53")],
+        }
+    }
+
+    #[test]
+    fn serializes_with_name_compression() {
+        let bytes = response().to_bytes().unwrap();
+        let expected = [
+            &b"\x12\x34\x81\x80\x00\x01\x00\x02\x00\x01\x00\x01"[..],
+            b"\x07example\x03com\x00\x00\x01\x00\x01",
+            b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\xc0\x00\x02\x01",
+            // SRV is not an RFC 1035 type, so only its owner name is compressed.
+            b"\x04_sip\x04_tcp\xc0\x0c\x00\x21\x00\x01\x00\x00\x01\x2c\x00\x17",
+            b"\x00\x0a\x00\x14\x13\xc4\x03sip\x07example\x03com\x00",
+            b"\xc0\x0c\x00\x02\x00\x01\x00\x00\x01\x2c\x00\x06\x03ns1\xc0\x0c",
+            b"\xc0\x66\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\xc0\x00\x02\x35",
+        ].concat();
+        assert_eq!(bytes, expected);
+
+        let decoded = DNSPacket::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.header.id(), 0x1234);
+        assert_eq!(decoded.answers.len(), 2);
+        assert_eq!(decoded.additionals[0].name, "ns1.example.com.".parse::<Name>().unwrap());
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn counts_come_from_the_sections() {
+        let mut packet = response();
+        packet.header.num_answers = 7;
+        packet.answers.pop();
+        packet.additionals.clear();
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(&bytes[4..12], b"\x00\x01\x00\x01\x00\x01\x00\x00");
+        assert!(DNSPacket::try_from(bytes.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn keeps_unknown_types_and_classes() {
+        let bytes = [
+            &b"\x00\x01\x81\x80\x00\x01\x00\x02\x00\x00\x00\x00"[..],
+            b"\x07example\x03com\x00\xff\xfe\xff\x00",
+            b"\xc0\x0c\xff\xfe\xff\x00\x00\x00\x01\x2c\x00\x03\xab\xcd\xef",
+            b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\xc0\x00\x02\x01",
+        ].concat();
+        let packet = DNSPacket::try_from(bytes.as_slice()).unwrap();
+        assert_eq!((packet.questions[0].kind, packet.questions[0].class), (Kind::Unknown(65534), Class::Unknown(65280)));
+        let unknown = &packet.answers[0];
+        assert_eq!((unknown.kind, unknown.class), (Kind::Unknown(65534), Class::Unknown(65280)));
+        assert!(matches!(&unknown.data, Data::Other(data) if data == b"\xab\xcd\xef"));
+        assert!(matches!(packet.answers[1].data, Data::IPv4(ip) if ip == Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn encodes_meta_qtypes() {
+        for (kind, value) in [(Kind::ANY, b"\x00\xff"), (Kind::AXFR, b"\x00\xfc"), (Kind::IXFR, b"\x00\xfb")] {
+            let question = DNSQuestion { name: Name::root(), kind, class: Class::ANY };
+            let mut writer = Writer::new();
+            question.encode(&mut writer);
+            assert_eq!(writer.into_bytes(), [&b"\x00"[..], value, b"\x00\xff"].concat());
+        }
+    }
+
+    #[test]
+    fn any_answers_leave_out_the_opt_record() {
+        let mut packet = response();
+        packet.answers.push(record("example.com. 300 IN MX 10 mail.example.com."));
+        packet.set_edns(Some(Edns::default()));
+        assert_eq!(packet.get_answers(Kind::ANY).len(), 3);
+        assert_eq!(packet.get_answers(Kind::MX).len(), 1);
+        packet.answers.append(&mut packet.additionals);
+        assert_eq!(packet.get_answers(Kind::ANY).len(), 4);
+    }
+
+    #[test]
+    fn displays_like_dig() {
+        let mut packet = response();
+        packet.set_edns(Some(Edns { dnssec_ok: true, options: vec![EdnsOption::Nsid(b"ns1".to_vec())], ..Edns::default() }));
+        let expected = "\
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4660
+;; flags: qr rd ra; QUERY: 1, ANSWER: 2, AUTHORITY: 1, ADDITIONAL: 2
+
+;; OPT PSEUDOSECTION:
+; EDNS: version: 0, flags: do; udp: 1232
+; NSID: 6E7331 (\"ns1\")
+
+;; QUESTION SECTION:
+;example.com.\tIN\tA
+
+;; ANSWER SECTION:
+example.com.\t300\tIN\tA\t192.0.2.1
+_sip._tcp.example.com.\t300\tIN\tSRV\t10 20 5060 sip.example.com.
+
+;; AUTHORITY SECTION:
+example.com.\t300\tIN\tNS\tns1.example.com.
+
+;; ADDITIONAL SECTION:
+ns1.example.com.\t300\tIN\tA\t192.0.2.53";
+        assert_eq!(packet.to_string(), expected);
+
+        let empty = DNSPacket {
+            header: DNSHeader { id: 1, flags: Flags(0x8005), ..DNSHeader::default() },
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        };
+        assert_eq!(
+            empty.to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: REFUSED, id: 1\n;; flags: qr; QUERY: 0, ANSWER: 0, AUTHORITY: 0, ADDITIONAL: 0"
+        );
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut packet = response();
+        packet.answers.push(record("example.com. 60 IN TXT \"v=spf1 -all\" \"\""));
+        packet.authorities.push(record("example.com. 3600 IN SOA ns1.example.com. admin.example.com. 1 7200 900 1209600 300"));
+        packet.set_edns(Some(Edns {
+            extended_rcode: 1,
+            dnssec_ok: true,
+            options: vec![EdnsOption::Cookie { client: [7; 8], server: Some(vec![9; 8]) }],
+            ..Edns::default()
+        }));
+        let bytes = packet.to_bytes().unwrap();
+        let decoded = DNSPacket::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.rcode(), Rcode::BADVERS);
+        assert_eq!(decoded.edns(), packet.edns());
+        assert_eq!(decoded.to_string(), packet.to_string());
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn edns_lives_in_a_single_opt_record() {
+        let mut packet = response();
+        assert_eq!(packet.edns(), None);
+        let edns = Edns { udp_payload_size: 4096, options: vec![EdnsOption::Padding(8)], ..Edns::default() };
+        packet.set_edns(Some(edns.clone()));
+        packet.set_edns(Some(edns.clone()));
+        assert_eq!(packet.additionals.iter().filter(|record| record.kind == Kind::OPT).count(), 1);
+        assert_eq!(packet.additionals.len(), 2);
+        assert_eq!(packet.edns(), Some(edns));
+        packet.set_edns(None);
+        assert_eq!(packet.edns(), None);
+        assert_eq!(packet.additionals.len(), 1);
+    }
+
+    #[test]
+    fn rcode_takes_the_upper_bits_from_the_opt_record() {
+        let mut packet = response();
+        packet.header.flags = Flags(0x8187);
+        assert_eq!(packet.rcode(), Rcode::YXRRSET);
+        packet.set_edns(Some(Edns { extended_rcode: 1, ..Edns::default() }));
+        assert_eq!(packet.rcode(), Rcode::BADCOOKIE);
+        assert!(packet.to_string().contains("status: BADCOOKIE"));
+        packet.header.flags = Flags(0x8180);
+        assert_eq!(packet.rcode(), Rcode::BADVERS);
+        packet.set_edns(Some(Edns::default()));
+        assert_eq!(packet.rcode(), Rcode::NOERROR);
+    }
+}
@@ -1,6 +1,10 @@
+use std::fmt::{Display, Formatter};
+use crate::dns_structs::name::Name;
 use crate::dns_structs::reader::{NameDecoder, Reader};
+use crate::dns_structs::writer::{EncodeError, SliceWriter, Writer};
+use crate::errors::Error;
 use crate::enums::{Class};
-use crate::{Kind, transform_result};
+use crate::Kind;
 
 /// DNS Question
 ///
@@ -10,7 +14,7 @@ pub struct DNSQuestion {
     /// Domain Name
     ///
     /// A domain name represented as a sequence of labels separated by dots. Each label consists of a length octet followed by that number of octets. The domain name terminates with the zero length octet for the null label of the root.
-    pub(crate) name: String,
+    pub(crate) name: Name,
 
     /// Record Type
     ///
@@ -24,35 +28,49 @@ pub struct DNSQuestion {
 }
 
 impl DNSQuestion {
-    fn encode_name(&self) -> Vec<u8> {
-        self.name.split('.')
-            .flat_map(|part| std::iter::once(part.len() as u8).chain(part.bytes()))
-            .chain(std::iter::once(0))
-            .collect()
+    /// Writes the question into `writer` without compression, for `encode_query`.
+    pub fn encode_into(&self, writer: &mut SliceWriter) -> Result<(), EncodeError> {
+        writer.write_name(&self.name)?;
+        writer.write_u16(u16::from(self.kind))?;
+        writer.write_u16(u16::from(self.class))
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = self.encode_name();
-        bytes.extend_from_slice(&(self.kind as u16).to_be_bytes());
-        bytes.extend_from_slice(&(self.class as u16).to_be_bytes());
-        bytes
+        let mut writer = Writer::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// Writes the question in wire format, compressing its name.
+    pub fn encode(&self, writer: &mut Writer) {
+        writer.write_name(&self.name, true);
+        writer.write_u16(u16::from(self.kind));
+        writer.write_u16(u16::from(self.class));
+    }
+}
+
+/// Writes the question as `name CLASS TYPE`, separated by tabs.
+impl Display for DNSQuestion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)?;
+        write!(f, "\t{}\t{}", self.class, self.kind)
     }
 }
 
-impl TryFrom<&mut Reader> for DNSQuestion {
-    type Error = String;
+impl TryFrom<&mut Reader<'_>> for DNSQuestion {
+    type Error = Error;
 
     fn try_from(reader: &mut Reader) -> Result<Self, Self::Error> {
-        let name = transform_result!(reader.decode_name())?;
+        let name = reader.decode_name()?;
 
-        let kind: u16 = transform_result!("Error reading kind from the response", reader.next_u16())?;
-        let class: u16 = transform_result!("Error reading class from the response", reader.next_u16())?;
+        let kind: u16 = reader.next_u16()?;
+        let class: u16 = reader.next_u16()?;
 
         Ok(
             Self {
                 name,
-                kind: Kind::try_from(kind).map_err(|_| "Invalid kind")?,
-                class: Class::try_from(class).map_err(|_| "Invalid class")?,
+                kind: Kind::from(kind),
+                class: Class::from(class),
             }
         )
     }
@@ -1,95 +1,414 @@
-use std::io::{Cursor, Error as IoError, Read, Seek, SeekFrom};
-use std::{io, result};
-use std::string::FromUtf8Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Error as IoError, Read};
+use std::ops::Range;
+use std::result;
+use crate::dns_structs::name::Name;
+use crate::Kind;
 
 type Result<T> = result::Result<T, DecodeError>;
-pub struct Reader(Cursor<Vec<u8>>);
 
-impl Reader {
-    pub fn new(inner: Vec<u8>) -> Self {
-        Reader(Cursor::new(inner))
+/// A cursor over the bytes of a DNS message.
+///
+/// The reader borrows the message, so names can follow compression pointers to any earlier offset.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// The end of the region that can be read, set while decoding the RDATA of a record.
+    limit: Option<usize>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, position: 0, limit: None }
+    }
+
+    /// Returns the whole message being read.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the current offset of the reader from the start of the message.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the reader to the given offset from the start of the message.
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Returns the number of bytes that can still be read, up to the end of the current RDATA if any.
+    pub fn remaining(&self) -> usize {
+        self.end().saturating_sub(self.position)
+    }
+
+    fn end(&self) -> usize {
+        self.limit.unwrap_or(self.bytes.len())
+    }
+
+    /// Runs `decode` with reads restricted to the next `len` bytes and checks that it consumed all of them.
+    ///
+    /// This is used to keep the decoding of a record's RDATA inside its RDLENGTH.
+    pub fn with_limit<T>(&mut self, len: usize, decode: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let start = self.position;
+        let end = start + len;
+        if end > self.end() {
+            return Err(DecodeError::new(start, DecodeErrorKind::UnexpectedEof));
+        }
+        let outer_limit = self.limit.replace(end);
+        let result = decode(self);
+        self.limit = outer_limit;
+        let value = result?;
+        if self.position != end {
+            return Err(DecodeError::new(self.position, DecodeErrorKind::TrailingData { unread: end - self.position }));
+        }
+        Ok(value)
+    }
+
+    /// Returns the next `len` bytes and advances past them.
+    pub fn next_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        let start = self.position;
+        if len > self.remaining() {
+            let kind = if self.limit.is_some() { DecodeErrorKind::RdataOverrun } else { DecodeErrorKind::UnexpectedEof };
+            return Err(DecodeError::new(start, kind));
+        }
+        self.position += len;
+        Ok(&self.bytes[start..start + len])
     }
 
-    fn next(&mut self) -> io::Result<u8> {
-        let mut buffer = [0u8];
-        self.0.read_exact(&mut buffer)?;
-        Ok(buffer[0])
+    pub fn next_u8(&mut self) -> Result<u8> {
+        Ok(self.next_slice(1)?[0])
     }
 
-    pub fn next_u16(&mut self) -> io::Result<u16> {
-        let mut buffer = [0u8;2];
-        self.0.read_exact(&mut buffer)?;
-        Ok(u16::from_be_bytes(buffer))
+    pub fn next_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.next_array()?))
     }
 
-    pub fn next_u32(&mut self) -> io::Result<u32> {
-        let mut buffer = [0u8;4];
-        self.0.read_exact(&mut buffer)?;
-        Ok(u32::from_be_bytes(buffer))
+    pub fn next_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.next_array()?))
+    }
+
+    /// Reads exactly `N` bytes.
+    pub fn next_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buffer = [0u8; N];
+        buffer.copy_from_slice(self.next_slice(N)?);
+        Ok(buffer)
+    }
+
+    /// Reads exactly `len` bytes.
+    pub fn next_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        Ok(self.next_slice(len)?.to_vec())
+    }
+
+    /// Reads a `<character-string>`: a single length octet followed by that number of octets.
+    pub fn next_character_string(&mut self) -> Result<Vec<u8>> {
+        let len = self.next_u8()?;
+        self.next_bytes(len as usize)
+    }
+
+    /// Walks the name at the current position without allocating, calling `visit` with every label,
+    /// and leaves the reader right after the name.
+    ///
+    /// Compression pointers must point strictly before the label sequence that contains them, so
+    /// every jump goes further back in the message and loops are impossible.
+    pub fn walk_name(&mut self, mut visit: impl FnMut(usize, &'a [u8]) -> Result<()>) -> Result<()> {
+        let end_of_name = walk_name(self.bytes, self.position, self.end(), &mut visit)?;
+        self.position = end_of_name;
+        Ok(())
+    }
+
+    /// Skips the name at the current position, checking that it is well formed.
+    pub fn skip_name(&mut self) -> Result<()> {
+        self.walk_name(|_, _| Ok(()))
     }
 }
 
-impl Read for Reader {
+impl Read for Reader<'_> {
     fn read(&mut self, buf: &mut [u8]) -> result::Result<usize, IoError> {
-        self.0.read(buf)
+        let len = buf.len().min(self.remaining());
+        buf[..len].copy_from_slice(&self.bytes[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
     }
 }
 
-impl Seek for Reader {
-    fn seek(&mut self, pos: SeekFrom) -> result::Result<u64, IoError> {
-        self.0.seek(pos)
+/// Walks the name starting at `start` in `bytes`, calling `visit` with the offset and content of every
+/// label, and returns the offset right after the name.
+///
+/// `limit` bounds the part of the name before the first compression pointer, which is the part that
+/// belongs to the field being decoded.
+pub(crate) fn walk_name<'a>(
+    bytes: &'a [u8],
+    start: usize,
+    limit: usize,
+    visit: &mut impl FnMut(usize, &'a [u8]) -> Result<()>,
+) -> Result<usize> {
+    let mut position = start;
+    let mut sequence_start = position;
+    let mut end_of_name = None;
+    let mut name_len = 1;
+    loop {
+        // Returns the bytes in `range`, failing if they run past the message or, before the first
+        // pointer, past the current RDATA.
+        let get = |range: Range<usize>| {
+            let end = if end_of_name.is_none() { limit } else { bytes.len() };
+            match bytes.get(range.clone()) {
+                Some(slice) if range.end <= end => Ok(slice),
+                Some(_) => Err(DecodeError::new(position, DecodeErrorKind::RdataOverrun)),
+                None => Err(DecodeError::new(position, DecodeErrorKind::UnexpectedEof)),
+            }
+        };
+        let length = get(position..position + 1)?[0];
+        match length >> 6 {
+            0b00 if length == 0 => {
+                position += 1;
+                break;
+            }
+            0b00 => {
+                // The two type bits leave six bits for the length, so it never exceeds `MAX_LABEL_LEN`.
+                let length = length as usize;
+                name_len += length + 1;
+                if name_len > MAX_NAME_LEN {
+                    return Err(DecodeError::new(position, DecodeErrorKind::NameTooLong(name_len)));
+                }
+                visit(position, get(position + 1..position + 1 + length)?)?;
+                position += 1 + length;
+            }
+            0b11 => {
+                let low = get(position + 1..position + 2)?[0];
+                let target = ((length & 0b0011_1111) as usize) << 8 | low as usize;
+                if target > position {
+                    return Err(DecodeError::new(position, DecodeErrorKind::ForwardPointer { target }));
+                }
+                if target >= sequence_start {
+                    return Err(DecodeError::new(position, DecodeErrorKind::PointerLoop { target }));
+                }
+                end_of_name.get_or_insert(position + 2);
+                sequence_start = target;
+                position = target;
+            }
+            bits => return Err(DecodeError::new(position, DecodeErrorKind::ReservedLabelType(bits))),
+        }
     }
+    Ok(end_of_name.unwrap_or(position))
 }
 
+/// The maximum length of a label in octets (RFC 1035 section 2.3.4).
+pub const MAX_LABEL_LEN: usize = 63;
+/// The maximum length of a domain name in octets, as it appears on the wire (RFC 1035 section 2.3.4).
+pub const MAX_NAME_LEN: usize = 255;
+
+/// An error found while decoding a message, along with the offset of the offending byte.
 #[derive(Debug)]
-pub enum DecodeError {
-    Io(IoError),
-    Utf8(FromUtf8Error),
+pub struct DecodeError {
+    /// The offset from the start of the message where the error was found.
+    pub offset: usize,
+    /// What went wrong.
+    pub kind: DecodeErrorKind,
 }
 
-impl From<IoError> for DecodeError {
-    fn from(err: IoError) -> DecodeError {
-        DecodeError::Io(err)
+impl DecodeError {
+    pub fn new(offset: usize, kind: DecodeErrorKind) -> Self {
+        Self { offset, kind }
     }
 }
 
-impl From<FromUtf8Error> for DecodeError {
-    fn from(err: FromUtf8Error) -> DecodeError {
-        DecodeError::Utf8(err)
+#[derive(Debug)]
+pub enum DecodeErrorKind {
+    /// The message ended in the middle of a field.
+    UnexpectedEof,
+    /// A field of a record's data runs past the end given by its RDLENGTH.
+    RdataOverrun,
+    /// The data of a record ended before its RDLENGTH.
+    TrailingData {
+        /// The number of bytes left unread.
+        unread: usize,
+    },
+    /// The RDLENGTH of a fixed size record does not match its type.
+    RdataLength {
+        /// The type of the record.
+        kind: Kind,
+        /// The length required by the type.
+        expected: usize,
+        /// The RDLENGTH of the record.
+        actual: usize,
+    },
+    /// A service parameter of an SVCB or HTTPS record has an invalid value.
+    InvalidSvcParam {
+        /// The key of the parameter.
+        key: u16,
+    },
+    /// The service parameters of an SVCB or HTTPS record are not in strictly ascending key order.
+    UnsortedSvcParams {
+        /// The first key out of order.
+        key: u16,
+    },
+    /// A window of an NSEC or NSEC3 type bit map has a length of 0 or over 32, or does not come after
+    /// the previous window.
+    InvalidTypeBitmap {
+        /// The window number.
+        window: u8,
+    },
+    /// A compression pointer points after itself.
+    ForwardPointer {
+        /// The offset the pointer points to.
+        target: usize,
+    },
+    /// A compression pointer points back into the name that is being decoded, which would loop forever.
+    PointerLoop {
+        /// The offset the pointer points to.
+        target: usize,
+    },
+    /// The label type bits `0b01` and `0b10` are reserved (RFC 6891 section 5). A length octet
+    /// above 63 always falls in one of these types or is a pointer.
+    ReservedLabelType(u8),
+    /// A name is longer than 255 octets.
+    NameTooLong(usize),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeErrorKind::UnexpectedEof => write!(f, "unexpected end of message"),
+            DecodeErrorKind::RdataOverrun => write!(f, "field runs past the end of the record data"),
+            DecodeErrorKind::TrailingData { unread } => write!(f, "{} unread bytes at the end of the record data", unread),
+            DecodeErrorKind::RdataLength { kind, expected, actual } =>
+                write!(f, "{} record data must be {} bytes long, got {}", kind, expected, actual),
+            DecodeErrorKind::InvalidSvcParam { key } => write!(f, "invalid value for svc param key{}", key),
+            DecodeErrorKind::UnsortedSvcParams { key } => write!(f, "svc param key{} out of order", key),
+            DecodeErrorKind::InvalidTypeBitmap { window } => write!(f, "invalid type bit map window {}", window),
+            DecodeErrorKind::ForwardPointer { target } => write!(f, "compression pointer to offset {} points forward", target),
+            DecodeErrorKind::PointerLoop { target } => write!(f, "compression pointer to offset {} loops", target),
+            DecodeErrorKind::ReservedLabelType(bits) => write!(f, "reserved label type {:#04b}", bits),
+            DecodeErrorKind::NameTooLong(len) => write!(f, "name of {} bytes exceeds 255 bytes", len),
+        }
     }
 }
 
 pub trait NameDecoder {
-    fn decode_name(&mut self) -> Result<String>;
-    fn decode_compressed_name(&mut self, length: u8) -> Result<String>;
+    fn decode_name(&mut self) -> Result<Name>;
 }
 
-impl NameDecoder for Reader {
-    fn decode_name(&mut self) -> Result<String> {
-        let mut parts: Vec<String> = Vec::new();
-        while let Ok(length) = self.next() {
-            if length == 0 {
-                break;
-            }
-            if length & 0b1100_0000 != 0 {
-                parts.push(self.decode_compressed_name(length)?);
-                break;
-            } else {
-                let mut buffer = vec![0; length as usize];
-                self.0.read_exact(&mut buffer)?;
-                parts.push(String::from_utf8(buffer)?);
-            }
+impl NameDecoder for Reader<'_> {
+    /// Decodes a possibly compressed domain name, leaving the reader right after it.
+    fn decode_name(&mut self) -> Result<Name> {
+        let mut wire = Vec::new();
+        self.walk_name(|_, label| {
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+            Ok(())
+        })?;
+        wire.push(0);
+        Ok(Name::from_wire(wire))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8], start: usize) -> Result<Name> {
+        let mut reader = Reader::new(bytes);
+        reader.set_position(start);
+        reader.decode_name()
+    }
+
+    fn error_kind(bytes: &[u8], start: usize) -> DecodeErrorKind {
+        decode(bytes, start).expect_err("the name should not decode").kind
+    }
+
+    #[test]
+    fn decodes_compressed_name() {
+        let bytes = b"\x07example\x03com\x00\x03www\xc0\x00";
+        let mut reader = Reader::new(bytes);
+        assert_eq!(reader.decode_name().unwrap(), "example.com.".parse().unwrap());
+        assert_eq!(reader.decode_name().unwrap(), "www.example.com.".parse().unwrap());
+        assert_eq!(reader.position(), bytes.len());
+    }
+
+    #[test]
+    fn rejects_truncated_names() {
+        assert!(matches!(error_kind(b"\x03ww", 0), DecodeErrorKind::UnexpectedEof));
+        assert!(matches!(error_kind(b"\x03www", 0), DecodeErrorKind::UnexpectedEof));
+        assert!(matches!(error_kind(b"\x00\xc0", 1), DecodeErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn rejects_names_past_the_record_data() {
+        let mut reader = Reader::new(b"\x03www\x00");
+        let error = reader.with_limit(3, |reader| reader.decode_name()).unwrap_err();
+        assert!(matches!(error.kind, DecodeErrorKind::RdataOverrun));
+    }
+
+    #[test]
+    fn rejects_pointer_to_itself() {
+        assert!(matches!(error_kind(b"\xc0\x00", 0), DecodeErrorKind::PointerLoop { target: 0 }));
+        assert!(matches!(error_kind(b"\x00\xc0\x01", 1), DecodeErrorKind::PointerLoop { target: 1 }));
+    }
+
+    #[test]
+    fn rejects_pointer_into_the_same_name() {
+        assert!(matches!(error_kind(b"\x01a\xc0\x00", 0), DecodeErrorKind::PointerLoop { target: 0 }));
+        // A loop through two pointers always has one of them pointing forward.
+        let bytes = b"\x01a\xc0\x04\x01b\xc0\x00";
+        assert!(matches!(error_kind(bytes, 4), DecodeErrorKind::ForwardPointer { target: 4 }));
+    }
+
+    #[test]
+    fn rejects_forward_pointers() {
+        assert!(matches!(error_kind(b"\xc0\x05\x00\x00\x00\x00", 0), DecodeErrorKind::ForwardPointer { target: 5 }));
+    }
+
+    #[test]
+    fn rejects_reserved_label_types() {
+        assert!(matches!(error_kind(b"\x40", 0), DecodeErrorKind::ReservedLabelType(0b01)));
+        assert!(matches!(error_kind(b"\x80", 0), DecodeErrorKind::ReservedLabelType(0b10)));
+    }
+
+    #[test]
+    fn rejects_names_over_255_bytes() {
+        let mut bytes = Vec::new();
+        for _ in 0..4 {
+            bytes.push(63);
+            bytes.extend_from_slice(&[b'a'; 63]);
+        }
+        bytes.push(0);
+        assert!(matches!(error_kind(&bytes, 0), DecodeErrorKind::NameTooLong(257)));
+
+        // A name made long by pointers to earlier names is caught too.
+        let mut bytes = vec![63];
+        bytes.extend_from_slice(&[b'a'; 63]);
+        bytes.push(0);
+        let mut previous = 0;
+        for _ in 0..3 {
+            let start = bytes.len();
+            bytes.push(63);
+            bytes.extend_from_slice(&[b'a'; 63]);
+            bytes.extend_from_slice(&[0xc0, previous as u8]);
+            previous = start;
         }
-        let name = parts.join(".");
-        Ok(name)
+        assert!(matches!(error_kind(&bytes, previous), DecodeErrorKind::NameTooLong(257)));
     }
 
-    fn decode_compressed_name(&mut self, length: u8) -> Result<String> {
-        let pointer = ((length as u16) & 0b0011_1111) << 8 | self.next()? as u16;
-        let current_pos = self.0.position();
-        self.0.seek(SeekFrom::Start(pointer as u64))?;
-        let result = self.decode_name()?;
-        self.0.seek(SeekFrom::Start(current_pos))?;
-        Ok(result)
+    #[test]
+    fn accepts_names_of_255_bytes() {
+        let mut bytes = Vec::new();
+        for _ in 0..3 {
+            bytes.push(63);
+            bytes.extend_from_slice(&[b'a'; 63]);
+        }
+        bytes.push(61);
+        bytes.extend_from_slice(&[b'a'; 61]);
+        bytes.push(0);
+        assert_eq!(bytes.len(), MAX_NAME_LEN);
+        assert_eq!(decode(&bytes, 0).unwrap().wire(), bytes.as_slice());
     }
 }
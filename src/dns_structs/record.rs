@@ -1,40 +1,567 @@
 use std::fmt::{Display, Formatter};
-use std::io::Read;
 use std::net::{Ipv4Addr, Ipv6Addr};
-use crate::dns_structs::reader::{NameDecoder, Reader};
-use crate::dns_structs::record::Data::{Host, IPv4, IPv6, Other};
+use std::str::FromStr;
+use crate::dns_structs::edns::EdnsOption;
+use crate::dns_structs::name::Name;
+use crate::dns_structs::reader::{DecodeError, DecodeErrorKind, NameDecoder, Reader};
+use crate::dns_structs::record::Data::*;
+use crate::dns_structs::svcb::SvcParam;
+use crate::dns_structs::writer::{EncodeError, Writer};
+use crate::dns_structs::zone::{parse_record, ZoneErrorKind};
+use crate::errors::Error;
+use crate::dns_structs::text::{write_base32hex, write_base64, write_character_string, write_hex, write_timestamp};
 use crate::enums::Class;
-use crate::{Kind, transform_result};
+use crate::Kind;
 
 /// An enumeration representing the different types of data that can be stored in a DNS record.
 #[derive(Debug, Clone)]
 pub enum Data {
-    /// A host name.
-    Host(String),
+    /// A host name, used by NS, CNAME, PTR, DNAME and the obsolete mailbox records (MB, MD, MF, MG, MR).
+    Host(Name),
     /// An IPv4 address.
     IPv4(Ipv4Addr),
     /// An IPv6 address.
     IPv6(Ipv6Addr),
-    /// Other data.
+    /// A mail exchanger (MX) or key exchanger (KX) with its preference.
+    Mx {
+        /// Lower values are preferred.
+        preference: u16,
+        /// The host willing to act as exchanger.
+        exchange: Name,
+    },
+    /// The start of a zone of authority (SOA).
+    Soa {
+        /// The name server that was the original or primary source of data for this zone.
+        mname: Name,
+        /// The mailbox of the person responsible for this zone.
+        rname: Name,
+        /// The version number of the original copy of the zone.
+        serial: u32,
+        /// Seconds before the zone should be refreshed.
+        refresh: u32,
+        /// Seconds before a failed refresh should be retried.
+        retry: u32,
+        /// Seconds after which the zone is no longer authoritative.
+        expire: u32,
+        /// The TTL used for negative responses.
+        minimum: u32,
+    },
+    /// The mailboxes of a mailing list (MINFO).
+    Minfo {
+        /// The mailbox responsible for the mailing list.
+        rmailbx: Name,
+        /// The mailbox that receives errors related to the mailing list.
+        emailbx: Name,
+    },
+    /// One or more `<character-string>`s (TXT).
+    Txt(Vec<Vec<u8>>),
+    /// Host information (HINFO).
+    Hinfo {
+        /// The CPU type.
+        cpu: Vec<u8>,
+        /// The operating system type.
+        os: Vec<u8>,
+    },
+    /// The location of a service (SRV).
+    Srv {
+        /// Lower values are preferred.
+        priority: u16,
+        /// Relative weight for entries with the same priority.
+        weight: u16,
+        /// The port of the service.
+        port: u16,
+        /// The host providing the service.
+        target: Name,
+    },
+    /// A Naming Authority Pointer (NAPTR).
+    Naptr {
+        /// The order in which the records must be processed.
+        order: u16,
+        /// The order in which records with equal `order` should be processed.
+        preference: u16,
+        /// Flags controlling the rewriting and interpretation of the fields.
+        flags: Vec<u8>,
+        /// The services available down this rewrite path.
+        services: Vec<u8>,
+        /// The substitution expression applied to the original string.
+        regexp: Vec<u8>,
+        /// The next domain name to query for.
+        replacement: Name,
+    },
+    /// An SSH public key fingerprint (SSHFP).
+    Sshfp {
+        /// The public key algorithm.
+        algorithm: u8,
+        /// The message digest algorithm used to calculate the fingerprint.
+        fingerprint_type: u8,
+        /// The fingerprint of the public key.
+        fingerprint: Vec<u8>,
+    },
+    /// A certificate association (TLSA and SMIMEA).
+    Tlsa {
+        /// How the certificate is matched against the end entity or trust anchor.
+        usage: u8,
+        /// Which part of the certificate is matched.
+        selector: u8,
+        /// How the certificate association is presented.
+        matching_type: u8,
+        /// The certificate association data.
+        data: Vec<u8>,
+    },
+    /// A Certification Authority Authorization (CAA).
+    Caa {
+        /// The issuer critical flag lives in the most significant bit.
+        flags: u8,
+        /// The property identifier, such as `issue` or `iodef`.
+        tag: Vec<u8>,
+        /// The value associated with the property.
+        value: Vec<u8>,
+    },
+    /// A Uniform Resource Identifier (URI).
+    Uri {
+        /// Lower values are preferred.
+        priority: u16,
+        /// Relative weight for entries with the same priority.
+        weight: u16,
+        /// The URI of the target.
+        target: Vec<u8>,
+    },
+    /// A public key (DNSKEY, CDNSKEY and KEY).
+    Dnskey {
+        /// Bit 7 marks a zone key and bit 15 a secure entry point.
+        flags: u16,
+        /// Always 3 for DNSSEC keys.
+        protocol: u8,
+        /// The public key's cryptographic algorithm.
+        algorithm: u8,
+        /// The public key material.
+        public_key: Vec<u8>,
+    },
+    /// A delegation signer digest (DS, CDS, DLV and TA).
+    Ds {
+        /// The key tag of the DNSKEY the digest refers to.
+        key_tag: u16,
+        /// The algorithm of the DNSKEY the digest refers to.
+        algorithm: u8,
+        /// The algorithm used to construct the digest.
+        digest_type: u8,
+        /// The digest of the DNSKEY.
+        digest: Vec<u8>,
+    },
+    /// A signature over an RRset (RRSIG and SIG).
+    Rrsig {
+        /// The type of the RRset covered by the signature.
+        type_covered: Kind,
+        /// The cryptographic algorithm used to create the signature.
+        algorithm: u8,
+        /// The number of labels in the original owner name.
+        labels: u8,
+        /// The TTL of the covered RRset as it appears in the authoritative zone.
+        original_ttl: u32,
+        /// The signature must not be used after this time, in seconds since the epoch.
+        expiration: u32,
+        /// The signature must not be used before this time, in seconds since the epoch.
+        inception: u32,
+        /// The key tag of the DNSKEY that validates this signature.
+        key_tag: u16,
+        /// The owner name of the DNSKEY that validates this signature.
+        signer_name: Name,
+        /// The cryptographic signature.
+        signature: Vec<u8>,
+    },
+    /// An authenticated denial of existence (NSEC).
+    Nsec {
+        /// The next owner name in the canonical ordering of the zone.
+        next_domain: Name,
+        /// The record types present at the owner name.
+        types: Vec<Kind>,
+    },
+    /// A hashed authenticated denial of existence (NSEC3).
+    Nsec3 {
+        /// The hash algorithm used to construct the hash values.
+        hash_algorithm: u8,
+        /// Bit 0 is the opt-out flag.
+        flags: u8,
+        /// The number of additional times the hash function has been performed.
+        iterations: u16,
+        /// The salt appended to the original owner name before hashing.
+        salt: Vec<u8>,
+        /// The next hashed owner name in hash order.
+        next_hashed_owner: Vec<u8>,
+        /// The record types present at the original owner name.
+        types: Vec<Kind>,
+    },
+    /// The NSEC3 parameters used by an authoritative server (NSEC3PARAM).
+    Nsec3Param {
+        /// The hash algorithm used to construct the hash values.
+        hash_algorithm: u8,
+        /// Reserved for future use, must be zero.
+        flags: u8,
+        /// The number of additional times the hash function has been performed.
+        iterations: u16,
+        /// The salt appended to the original owner name before hashing.
+        salt: Vec<u8>,
+    },
+    /// A service binding (SVCB and HTTPS).
+    Svcb {
+        /// Zero for AliasMode, the preference of the endpoint in ServiceMode otherwise.
+        priority: u16,
+        /// The alias target or alternative endpoint, `.` stands for the owner name.
+        target: Name,
+        /// The service parameters in ascending key order.
+        params: Vec<SvcParam>,
+    },
+    /// The options of an OPT pseudo-record, see `Edns::from_record` for the rest of its fields.
+    Opt(Vec<EdnsOption>),
+    /// The raw data of a record type this implementation does not decode, kept as is so it can be
+    /// re-encoded without loss.
     Other(Vec<u8>),
 }
 
+impl Data {
+    /// Writes the data in wire format.
+    ///
+    /// `compress` tells whether the domain names embedded in the data may be compressed, which
+    /// RFC 3597 only allows for the record types defined in RFC 1035.
+    pub fn encode(&self, writer: &mut Writer, compress: bool) -> Result<(), EncodeError> {
+        match self {
+            Host(host) => writer.write_name(host, compress),
+            IPv4(ip) => writer.write_bytes(&ip.octets()),
+            IPv6(ip) => writer.write_bytes(&ip.octets()),
+            Mx { preference, exchange } => {
+                writer.write_u16(*preference);
+                writer.write_name(exchange, compress);
+            }
+            Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                writer.write_name(mname, compress);
+                writer.write_name(rname, compress);
+                for value in [serial, refresh, retry, expire, minimum] {
+                    writer.write_u32(*value);
+                }
+            }
+            Minfo { rmailbx, emailbx } => {
+                writer.write_name(rmailbx, compress);
+                writer.write_name(emailbx, compress);
+            }
+            Txt(strings) => {
+                for string in strings {
+                    writer.write_character_string(string)?;
+                }
+            }
+            Hinfo { cpu, os } => {
+                writer.write_character_string(cpu)?;
+                writer.write_character_string(os)?;
+            }
+            Srv { priority, weight, port, target } => {
+                writer.write_u16(*priority);
+                writer.write_u16(*weight);
+                writer.write_u16(*port);
+                writer.write_name(target, compress);
+            }
+            Naptr { order, preference, flags, services, regexp, replacement } => {
+                writer.write_u16(*order);
+                writer.write_u16(*preference);
+                writer.write_character_string(flags)?;
+                writer.write_character_string(services)?;
+                writer.write_character_string(regexp)?;
+                writer.write_name(replacement, compress);
+            }
+            Sshfp { algorithm, fingerprint_type, fingerprint } => {
+                writer.write_u8(*algorithm);
+                writer.write_u8(*fingerprint_type);
+                writer.write_bytes(fingerprint);
+            }
+            Tlsa { usage, selector, matching_type, data } => {
+                writer.write_u8(*usage);
+                writer.write_u8(*selector);
+                writer.write_u8(*matching_type);
+                writer.write_bytes(data);
+            }
+            Caa { flags, tag, value } => {
+                writer.write_u8(*flags);
+                writer.write_character_string(tag)?;
+                writer.write_bytes(value);
+            }
+            Uri { priority, weight, target } => {
+                writer.write_u16(*priority);
+                writer.write_u16(*weight);
+                writer.write_bytes(target);
+            }
+            Dnskey { flags, protocol, algorithm, public_key } => {
+                writer.write_u16(*flags);
+                writer.write_u8(*protocol);
+                writer.write_u8(*algorithm);
+                writer.write_bytes(public_key);
+            }
+            Ds { key_tag, algorithm, digest_type, digest } => {
+                writer.write_u16(*key_tag);
+                writer.write_u8(*algorithm);
+                writer.write_u8(*digest_type);
+                writer.write_bytes(digest);
+            }
+            Rrsig { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature } => {
+                writer.write_u16(u16::from(*type_covered));
+                writer.write_u8(*algorithm);
+                writer.write_u8(*labels);
+                writer.write_u32(*original_ttl);
+                writer.write_u32(*expiration);
+                writer.write_u32(*inception);
+                writer.write_u16(*key_tag);
+                writer.write_name(signer_name, compress);
+                writer.write_bytes(signature);
+            }
+            Nsec { next_domain, types } => {
+                writer.write_name(next_domain, compress);
+                writer.write_bytes(&encode_type_bitmap(types));
+            }
+            Nsec3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                writer.write_u8(*hash_algorithm);
+                writer.write_u8(*flags);
+                writer.write_u16(*iterations);
+                writer.write_character_string(salt)?;
+                writer.write_character_string(next_hashed_owner)?;
+                writer.write_bytes(&encode_type_bitmap(types));
+            }
+            Nsec3Param { hash_algorithm, flags, iterations, salt } => {
+                writer.write_u8(*hash_algorithm);
+                writer.write_u8(*flags);
+                writer.write_u16(*iterations);
+                writer.write_character_string(salt)?;
+            }
+            Svcb { priority, target, params } => {
+                writer.write_u16(*priority);
+                writer.write_name(target, compress);
+                for param in params {
+                    let value = param.encode_value()?;
+                    writer.write_u16(u16::from(param.key()));
+                    writer.write_u16(u16::try_from(value.len()).map_err(|_| EncodeError::DataTooLong(value.len()))?);
+                    writer.write_bytes(&value);
+                }
+            }
+            Opt(options) => {
+                for option in options {
+                    let len = option.data_len();
+                    writer.write_u16(option.code());
+                    writer.write_u16(u16::try_from(len).map_err(|_| EncodeError::DataTooLong(len))?);
+                    option.encode_data_into(&mut writer.reserve_slice(len))?;
+                }
+            }
+            Other(raw_data) => writer.write_bytes(raw_data),
+        }
+        Ok(())
+    }
+
+    /// Computes the key tag of a `Dnskey` as described in RFC 4034 appendix B.
+    ///
+    /// Returns `None` for any other kind of data.
+    pub fn key_tag(&self) -> Option<u16> {
+        let Dnskey { flags, protocol, algorithm, public_key } = self else {
+            return None;
+        };
+        if *algorithm == 1 {
+            // RSA/MD5 uses the most significant 16 bits of the least significant 24 bits of the modulus.
+            let len = public_key.len();
+            return Some(if len >= 3 { u16::from_be_bytes([public_key[len - 3], public_key[len - 2]]) } else { 0 });
+        }
+        let rdata = flags.to_be_bytes().into_iter()
+            .chain([*protocol, *algorithm])
+            .chain(public_key.iter().copied());
+        let mut accumulator = rdata.enumerate()
+            .fold(0u32, |acc, (i, byte)| acc + if i & 1 == 0 { (byte as u32) << 8 } else { byte as u32 });
+        accumulator += (accumulator >> 16) & 0xffff;
+        Some((accumulator & 0xffff) as u16)
+    }
+}
+
+/// Decodes the NSEC/NSEC3 type bit map (RFC 4034 section 4.1.2) that takes the rest of the RDATA
+/// into the list of types it contains.
+///
+/// Every window must have a length from 1 to 32 and the windows must be in strictly ascending order.
+fn decode_type_bitmap(reader: &mut Reader) -> Result<Vec<Kind>, DecodeError> {
+    let mut types = Vec::new();
+    let mut previous: Option<u8> = None;
+    while reader.remaining() > 0 {
+        let offset = reader.position();
+        let window = reader.next_u8()?;
+        let len = reader.next_u8()?;
+        if !(1..=32).contains(&len) || previous.is_some_and(|previous| previous >= window) {
+            return Err(DecodeError::new(offset, DecodeErrorKind::InvalidTypeBitmap { window }));
+        }
+        previous = Some(window);
+        for (i, byte) in reader.next_slice(len as usize)?.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    let kind = (window as u16) << 8 | (i * 8 + bit) as u16;
+                    types.push(Kind::from(kind));
+                }
+            }
+        }
+    }
+    Ok(types)
+}
+
+/// Encodes a list of types as an NSEC/NSEC3 type bit map.
+fn encode_type_bitmap(types: &[Kind]) -> Vec<u8> {
+    let mut types: Vec<u16> = types.iter().map(|kind| u16::from(*kind)).collect();
+    types.sort_unstable();
+    types.dedup();
+    let mut bytes = Vec::new();
+    for window in types.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let mut bitmap = [0u8; 32];
+        for kind in window {
+            bitmap[(kind & 0xff) as usize / 8] |= 0x80 >> (kind & 0x07);
+        }
+        let len = bitmap.iter().rposition(|byte| *byte != 0).map_or(0, |last| last + 1);
+        bytes.push((window[0] >> 8) as u8);
+        bytes.push(len as u8);
+        bytes.extend_from_slice(&bitmap[..len]);
+    }
+    bytes
+}
+
+fn write_types(f: &mut Formatter<'_>, types: &[Kind]) -> std::fmt::Result {
+    types.iter().try_for_each(|kind| write!(f, " {}", kind))
+}
+
+fn write_salt(f: &mut Formatter<'_>, salt: &[u8]) -> std::fmt::Result {
+    if salt.is_empty() {
+        write!(f, "-")
+    } else {
+        write_hex(f, salt)
+    }
+}
+
+/// Writes the data in master-file format. The alternate flag (`{:#}`) shows internationalized
+/// names (`xn--` labels) in Unicode.
 impl Display for Data {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Host(hostname) => write!(f, "{}", hostname),
+            Host(hostname) => hostname.fmt(f),
             IPv4(ip) => write!(f, "{}", ip),
             IPv6(ip) => write!(f, "{}", ip),
-            Other(raw_data) => write!(f, "{}", String::from_utf8_lossy(raw_data))
+            Mx { preference, exchange } => {
+                write!(f, "{} ", preference)?;
+                exchange.fmt(f)
+            }
+            Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                mname.fmt(f)?;
+                write!(f, " ")?;
+                rname.fmt(f)?;
+                write!(f, " {} {} {} {} {}", serial, refresh, retry, expire, minimum)
+            }
+            Minfo { rmailbx, emailbx } => {
+                rmailbx.fmt(f)?;
+                write!(f, " ")?;
+                emailbx.fmt(f)
+            }
+            Txt(strings) => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write_character_string(f, string)?;
+                }
+                Ok(())
+            }
+            Hinfo { cpu, os } => {
+                write_character_string(f, cpu)?;
+                write!(f, " ")?;
+                write_character_string(f, os)
+            }
+            Srv { priority, weight, port, target } => {
+                write!(f, "{} {} {} ", priority, weight, port)?;
+                target.fmt(f)
+            }
+            Naptr { order, preference, flags, services, regexp, replacement } => {
+                write!(f, "{} {} ", order, preference)?;
+                write_character_string(f, flags)?;
+                write!(f, " ")?;
+                write_character_string(f, services)?;
+                write!(f, " ")?;
+                write_character_string(f, regexp)?;
+                write!(f, " ")?;
+                replacement.fmt(f)
+            }
+            Sshfp { algorithm, fingerprint_type, fingerprint } => {
+                write!(f, "{} {} ", algorithm, fingerprint_type)?;
+                write_hex(f, fingerprint)
+            }
+            Tlsa { usage, selector, matching_type, data } => {
+                write!(f, "{} {} {} ", usage, selector, matching_type)?;
+                write_hex(f, data)
+            }
+            Caa { flags, tag, value } => {
+                write!(f, "{} {} ", flags, String::from_utf8_lossy(tag))?;
+                write_character_string(f, value)
+            }
+            Uri { priority, weight, target } => {
+                write!(f, "{} {} ", priority, weight)?;
+                write_character_string(f, target)
+            }
+            Dnskey { flags, protocol, algorithm, public_key } => {
+                write!(f, "{} {} {} ", flags, protocol, algorithm)?;
+                write_base64(f, public_key)
+            }
+            Ds { key_tag, algorithm, digest_type, digest } => {
+                write!(f, "{} {} {} ", key_tag, algorithm, digest_type)?;
+                write_hex(f, digest)
+            }
+            Rrsig { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature } => {
+                write!(f, "{} {} {} {} ", type_covered, algorithm, labels, original_ttl)?;
+                write_timestamp(f, *expiration)?;
+                write!(f, " ")?;
+                write_timestamp(f, *inception)?;
+                write!(f, " {} ", key_tag)?;
+                signer_name.fmt(f)?;
+                write!(f, " ")?;
+                write_base64(f, signature)
+            }
+            Nsec { next_domain, types } => {
+                next_domain.fmt(f)?;
+                write_types(f, types)
+            }
+            Nsec3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                write_salt(f, salt)?;
+                write!(f, " ")?;
+                write_base32hex(f, next_hashed_owner)?;
+                write_types(f, types)
+            }
+            Nsec3Param { hash_algorithm, flags, iterations, salt } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                write_salt(f, salt)
+            }
+            Svcb { priority, target, params } => {
+                write!(f, "{} ", priority)?;
+                target.fmt(f)?;
+                params.iter().try_for_each(|param| write!(f, " {}", param))
+            }
+            // OPT has no presentation format, so its options are written as generic data.
+            Opt(_) => {
+                let mut writer = Writer::new();
+                self.encode(&mut writer, false).map_err(|_| std::fmt::Error)?;
+                write_generic(f, &writer.into_bytes())
+            }
+            Other(raw_data) => write_generic(f, raw_data),
         }
     }
 }
 
+/// Writes data in the generic RDATA presentation of RFC 3597 section 5.
+fn write_generic(f: &mut Formatter<'_>, data: &[u8]) -> std::fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        write!(f, " ")?;
+        write_hex(f, data)?;
+    }
+    Ok(())
+}
+
 /// A structure representing a DNS record.
 #[derive(Debug, Clone)]
 pub struct DNSRecord {
     /// The name associated with the record.
-    pub name: String,
+    pub name: Name,
     /// The type of the record.
     pub kind: Kind,
     /// The class of the record.
@@ -45,8 +572,55 @@ pub struct DNSRecord {
     pub data: Data,
 }
 
-impl TryFrom<&mut Reader> for DNSRecord {
-    type Error = String;
+impl DNSRecord {
+    /// Writes the record in wire format.
+    ///
+    /// The owner name is always compressed, names inside the data only for the record types
+    /// defined in RFC 1035 (see RFC 3597 section 4).
+    pub fn encode(&self, writer: &mut Writer) -> Result<(), EncodeError> {
+        writer.write_name(&self.name, true);
+        writer.write_u16(u16::from(self.kind));
+        writer.write_u16(u16::from(self.class));
+        writer.write_u32(self.ttl);
+        let length_offset = writer.len();
+        writer.write_u16(0);
+        let compress = matches!(
+            self.kind,
+            Kind::NS | Kind::MD | Kind::MF | Kind::CNAME | Kind::SOA | Kind::MB
+            | Kind::MG | Kind::MR | Kind::PTR | Kind::MINFO | Kind::MX
+        );
+        self.data.encode(writer, compress)?;
+        let data_len = writer.len() - length_offset - 2;
+        let data_len = u16::try_from(data_len).map_err(|_| EncodeError::DataTooLong(data_len))?;
+        writer.patch_u16(length_offset, data_len);
+        Ok(())
+    }
+}
+
+/// Writes the record in master-file format, `owner TTL CLASS TYPE RDATA`, separated by tabs like dig does.
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)?;
+        write!(f, "\t{}\t{}\t{}\t", self.ttl, self.class, self.kind)?;
+        self.data.fmt(f)
+    }
+}
+
+impl FromStr for DNSRecord {
+    type Err = ZoneErrorKind;
+
+    /// Parses a record in master-file format, like `www.example.com. 300 IN MX 10 mail.example.com.`.
+    ///
+    /// Names are taken as fully qualified and the TTL is required. The class may be left out, in
+    /// which case it is IN, and the TTL and the class may come in either order. Types and classes
+    /// can use the generic forms of RFC 3597, like `TYPE65534`, `CLASS3` and `\# 2 abcd` for the data.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_record(text)
+    }
+}
+
+impl TryFrom<&mut Reader<'_>> for DNSRecord {
+    type Error = Error;
 
     /// Attempts to create a `DNSRecord` from the given `Reader`.
     ///
@@ -56,47 +630,503 @@ impl TryFrom<&mut Reader> for DNSRecord {
     ///
     /// # Returns
     ///
-    /// A `Result` containing either a `DNSRecord` or an error message.
+    /// A `Result` containing either a `DNSRecord` or the error found while decoding it.
     fn try_from(reader: &mut Reader) -> Result<Self, Self::Error> {
-        let name = transform_result!(reader.decode_name())?;
-
-        let kind: u16 = transform_result!("Error reading kind from the response", reader.next_u16())?;
-        let class: u16 = transform_result!("Error reading class from the response", reader.next_u16())?;
-        let ttl: u32 = transform_result!("Error reading ttl from the response",reader.next_u32())?;
-        let data_len: u16 = transform_result!("Error reading data len from the response",reader.next_u16())?;
-
-        let kind = Kind::try_from(kind).map_err(|_| "Invalid kind")?;
-
-        let data = match kind {
-            Kind::NS => Host(transform_result!(reader.decode_name())?),
-            Kind::A => {
-                assert_eq!(data_len, 4);
-                let mut ip = [0u8; 4];
-                transform_result!("Error reading ip from the response",reader.read_exact(&mut ip))?;
-                IPv4(Ipv4Addr::from(ip))
-            }
-            Kind::AAAA => {
-                assert_eq!(data_len, 16);
-                let mut ip = [0u8; 16];
-                transform_result!("Error reading ip from the response",reader.read_exact(&mut ip))?;
-                IPv6(Ipv6Addr::from(ip))
-            },
-            Kind::CNAME => Host(transform_result!(reader.decode_name())?),
-            _ => {
-                let mut data = vec![0; data_len as usize];
-                transform_result!("Error reading data from the response",reader.read_exact(&mut data))?;
-                Other(data)
-            }
-        };
+        let name = reader.decode_name()?;
+
+        let kind: u16 = reader.next_u16()?;
+        let class: u16 = reader.next_u16()?;
+        let ttl: u32 = reader.next_u32()?;
+        let data_len: u16 = reader.next_u16()?;
+
+        let kind = Kind::from(kind);
+        let data = reader.with_limit(data_len as usize, |reader| decode_data(reader, kind))?;
 
         Ok(
             Self {
                 name,
                 kind,
-                class: Class::try_from(class).map_err(|_| "Invalid class")?,
+                class: Class::from(class),
                 ttl,
                 data,
             }
         )
     }
 }
+
+/// Decodes the RDATA of a record of the given type, the reader must be limited to the RDLENGTH of the record.
+pub(crate) fn decode_data(reader: &mut Reader, kind: Kind) -> Result<Data, DecodeError> {
+    let fixed_length = |reader: &Reader, expected: usize| {
+        let actual = reader.remaining();
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(DecodeError::new(reader.position(), DecodeErrorKind::RdataLength { kind, expected, actual }))
+        }
+    };
+    let data = match kind {
+        Kind::NS | Kind::CNAME | Kind::PTR | Kind::DNAME
+        | Kind::MB | Kind::MD | Kind::MF | Kind::MG | Kind::MR => Host(reader.decode_name()?),
+        Kind::A => {
+            fixed_length(reader, 4)?;
+            IPv4(Ipv4Addr::from(reader.next_array::<4>()?))
+        }
+        Kind::AAAA => {
+            fixed_length(reader, 16)?;
+            IPv6(Ipv6Addr::from(reader.next_array::<16>()?))
+        },
+        Kind::MX | Kind::KX => Mx {
+            preference: reader.next_u16()?,
+            exchange: reader.decode_name()?,
+        },
+        Kind::SOA => Soa {
+            mname: reader.decode_name()?,
+            rname: reader.decode_name()?,
+            serial: reader.next_u32()?,
+            refresh: reader.next_u32()?,
+            retry: reader.next_u32()?,
+            expire: reader.next_u32()?,
+            minimum: reader.next_u32()?,
+        },
+        Kind::MINFO => Minfo {
+            rmailbx: reader.decode_name()?,
+            emailbx: reader.decode_name()?,
+        },
+        Kind::TXT => {
+            let mut strings = Vec::new();
+            while reader.remaining() > 0 {
+                strings.push(reader.next_character_string()?);
+            }
+            Txt(strings)
+        }
+        Kind::HINFO => Hinfo {
+            cpu: reader.next_character_string()?,
+            os: reader.next_character_string()?,
+        },
+        Kind::SRV => Srv {
+            priority: reader.next_u16()?,
+            weight: reader.next_u16()?,
+            port: reader.next_u16()?,
+            target: reader.decode_name()?,
+        },
+        Kind::NAPTR => Naptr {
+            order: reader.next_u16()?,
+            preference: reader.next_u16()?,
+            flags: reader.next_character_string()?,
+            services: reader.next_character_string()?,
+            regexp: reader.next_character_string()?,
+            replacement: reader.decode_name()?,
+        },
+        Kind::SSHFP => Sshfp {
+            algorithm: reader.next_u8()?,
+            fingerprint_type: reader.next_u8()?,
+            fingerprint: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::TLSA | Kind::SMIMEA => Tlsa {
+            usage: reader.next_u8()?,
+            selector: reader.next_u8()?,
+            matching_type: reader.next_u8()?,
+            data: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::CAA => Caa {
+            flags: reader.next_u8()?,
+            tag: reader.next_character_string()?,
+            value: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::URI => Uri {
+            priority: reader.next_u16()?,
+            weight: reader.next_u16()?,
+            target: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::DNSKEY | Kind::CDNSKEY | Kind::KEY => Dnskey {
+            flags: reader.next_u16()?,
+            protocol: reader.next_u8()?,
+            algorithm: reader.next_u8()?,
+            public_key: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::DS | Kind::CDS | Kind::DLV | Kind::TA => Ds {
+            key_tag: reader.next_u16()?,
+            algorithm: reader.next_u8()?,
+            digest_type: reader.next_u8()?,
+            digest: reader.next_bytes(reader.remaining())?,
+        },
+        Kind::RRSIG | Kind::SIG => {
+            let type_covered = reader.next_u16()?;
+            Rrsig {
+                type_covered: Kind::from(type_covered),
+                algorithm: reader.next_u8()?,
+                labels: reader.next_u8()?,
+                original_ttl: reader.next_u32()?,
+                expiration: reader.next_u32()?,
+                inception: reader.next_u32()?,
+                key_tag: reader.next_u16()?,
+                signer_name: reader.decode_name()?,
+                signature: reader.next_bytes(reader.remaining())?,
+            }
+        }
+        Kind::NSEC => Nsec {
+            next_domain: reader.decode_name()?,
+            types: decode_type_bitmap(reader)?,
+        },
+        Kind::NSEC3 => Nsec3 {
+            hash_algorithm: reader.next_u8()?,
+            flags: reader.next_u8()?,
+            iterations: reader.next_u16()?,
+            salt: reader.next_character_string()?,
+            next_hashed_owner: reader.next_character_string()?,
+            types: decode_type_bitmap(reader)?,
+        },
+        Kind::NSEC3PARAM => Nsec3Param {
+            hash_algorithm: reader.next_u8()?,
+            flags: reader.next_u8()?,
+            iterations: reader.next_u16()?,
+            salt: reader.next_character_string()?,
+        },
+        Kind::SVCB | Kind::HTTPS => {
+            let priority = reader.next_u16()?;
+            let target = reader.decode_name()?;
+            let mut params: Vec<SvcParam> = Vec::new();
+            while reader.remaining() > 0 {
+                let offset = reader.position();
+                let key = reader.next_u16()?;
+                if params.last().is_some_and(|last| u16::from(last.key()) >= key) {
+                    return Err(DecodeError::new(offset, DecodeErrorKind::UnsortedSvcParams { key }));
+                }
+                let len = reader.next_u16()?;
+                let value = reader.next_bytes(len as usize)?;
+                let param = SvcParam::decode(key, &value)
+                    .ok_or(DecodeError::new(offset, DecodeErrorKind::InvalidSvcParam { key }))?;
+                params.push(param);
+            }
+            Svcb { priority, target, params }
+        }
+        Kind::OPT => {
+            let mut options = Vec::new();
+            while reader.remaining() > 0 {
+                let code = reader.next_u16()?;
+                let len = reader.next_u16()?;
+                let data = reader.next_slice(len as usize)?;
+                options.push(EdnsOption::decode(code, data));
+            }
+            Opt(options)
+        }
+        _ => Other(reader.next_bytes(reader.remaining())?),
+    };
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> DNSRecord {
+        text.parse().unwrap_or_else(|error| panic!("{text:?} should parse: {error}"))
+    }
+
+    /// Encodes `record` after a name that its data may point to and decodes it back.
+    fn wire_round_trip(record: &DNSRecord) -> DNSRecord {
+        let mut writer = Writer::new();
+        writer.write_name(&"example.com.".parse().unwrap(), false);
+        let start = writer.len();
+        record.encode(&mut writer).unwrap();
+        let bytes = writer.into_bytes();
+        let mut reader = Reader::new(&bytes);
+        reader.set_position(start);
+        let decoded = DNSRecord::try_from(&mut reader).unwrap();
+        assert_eq!(reader.remaining(), 0);
+        decoded
+    }
+
+    /// Decodes `rdata` as the data of a record of type `kind`, after `prefix` that names may point into.
+    fn decode(kind: Kind, prefix: &[u8], rdata: &[u8]) -> Data {
+        let bytes = [prefix, rdata].concat();
+        let mut reader = Reader::new(&bytes);
+        reader.set_position(prefix.len());
+        let data = reader.with_limit(rdata.len(), |reader| decode_data(reader, kind)).unwrap();
+        assert_eq!(reader.remaining(), 0);
+        data
+    }
+
+    /// Encodes `data` on its own, without compression.
+    fn encode(data: &Data) -> Vec<u8> {
+        let mut writer = Writer::new();
+        data.encode(&mut writer, false).unwrap();
+        writer.into_bytes()
+    }
+
+    fn wire(name: &str) -> Vec<u8> {
+        name.parse::<Name>().unwrap().wire().to_vec()
+    }
+
+    /// Records in the form `Display` writes them.
+    const RECORDS: &[&str] = &[
+        "example.com.\t300\tIN\tA\t192.0.2.1",
+        "example.com.\t300\tIN\tAAAA\t2001:db8::1",
+        "example.com.\t300\tIN\tNS\tns1.example.com.",
+        "example.com.\t300\tIN\tMX\t10 mail.example.com.",
+        "example.com.\t300\tIN\tSOA\tns1.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 300",
+        "list.example.com.\t300\tIN\tMINFO\towner.example.com. errors.example.com.",
+        "example.com.\t300\tIN\tTXT\t\"v=spf1 -all\" \"a \\\"quoted\\\" \\\\ string\" \"\\200\"",
+        "example.com.\t300\tIN\tHINFO\t\"PC\" \"Linux\"",
+        "_sip._tcp.example.com.\t300\tIN\tSRV\t10 20 5060 sip.example.com.",
+        "example.com.\t300\tIN\tNAPTR\t100 10 \"U\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" .",
+        "example.com.\t300\tIN\tSSHFP\t1 1 0123456789ABCDEF0123456789ABCDEF01234567",
+        "_443._tcp.example.com.\t300\tIN\tTLSA\t3 1 1 0123456789ABCDEF",
+        "example.com.\t300\tIN\tCAA\t0 issue \"letsencrypt.org\"",
+        "example.com.\t300\tIN\tURI\t10 1 \"https://example.com/\"",
+        "example.com.\t300\tIN\tDS\t370 13 2 BE74359954660069D5C63D200C39F5603827D7DD02B56F120EE9F3A8 6764247C",
+        "example.com.\t300\tIN\tRRSIG\tA 13 2 300 20240201000000 20240101000000 12345 example.com. c2lnbmF0dXJl",
+        "example.com.\t300\tIN\tNSEC\twww.example.com. A NS SOA MX RRSIG NSEC DNSKEY TYPE1234",
+        "example.com.\t300\tIN\tNSEC3\t1 0 10 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+        "example.com.\t300\tIN\tNSEC3PARAM\t1 0 10 AABBCCDD",
+        "example.com.\t300\tIN\tHTTPS\t1 . alpn=\"h2,h3\" port=8443 ipv4hint=192.0.2.1 ech=AEX+",
+        "example.com.\t300\tCH\tTXT\t\"x\"",
+        "example.com.\t300\tIN\tTYPE65534\t\\# 3 ABCDEF",
+        "example.com.\t300\tIN\tTYPE65534\t\\# 0",
+    ];
+
+    #[test]
+    fn presentation_round_trip() {
+        for text in RECORDS {
+            assert_eq!(parse(text).to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn wire_round_trip_keeps_presentation() {
+        for text in RECORDS {
+            assert_eq!(wire_round_trip(&parse(text)).to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn generic_data_of_known_types_is_decoded() {
+        assert_eq!(parse("example.com. 300 IN A \\# 4 C0000201").to_string(), "example.com.\t300\tIN\tA\t192.0.2.1");
+        let record = parse("example.com. 300 IN MX \\# 8 000A 04 6D61696C 00");
+        assert_eq!(record.to_string(), "example.com.\t300\tIN\tMX\t10 mail.");
+        assert!("example.com. 300 IN A \\# 3 C00002".parse::<DNSRecord>().is_err());
+        assert!("example.com. 300 IN TYPE65534 \\# 2 ABCDEF".parse::<DNSRecord>().is_err());
+    }
+
+    #[test]
+    fn unknown_types_and_classes_use_generic_presentation() {
+        let record = parse("example.com. 300 CLASS65280 TYPE65534 \\# 2 abcd");
+        assert_eq!(record.to_string(), "example.com.\t300\tCLASS65280\tTYPE65534\t\\# 2 ABCD");
+        assert_eq!(wire_round_trip(&record).to_string(), record.to_string());
+    }
+
+    #[test]
+    fn parse_errors_are_std_errors() -> Result<(), Box<dyn std::error::Error>> {
+        let record: DNSRecord = "example.com. 300 IN A 192.0.2.1".parse()?;
+        assert_eq!(record.kind, Kind::A);
+        let error: Box<dyn std::error::Error> = "example.com. 300 IN A x".parse::<DNSRecord>().unwrap_err().into();
+        assert_eq!(error.to_string(), "invalid IPv4 address \"x\"");
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_type_bitmaps() {
+        let decode = |bitmap: &[u8]| {
+            let mut rdata = b"\x00".to_vec();
+            rdata.extend_from_slice(bitmap);
+            let mut reader = Reader::new(&rdata);
+            reader.with_limit(rdata.len(), |reader| decode_data(reader, Kind::NSEC))
+        };
+        assert!(decode(b"\x00\x01\x40").is_ok());
+        assert!(matches!(decode(b"\x00\x00").unwrap_err().kind, DecodeErrorKind::InvalidTypeBitmap { window: 0 }));
+        assert!(matches!(decode(&[0, 33]).unwrap_err().kind, DecodeErrorKind::InvalidTypeBitmap { window: 0 }));
+        assert!(matches!(decode(b"\x01\x01\x40\x00\x01\x40").unwrap_err().kind, DecodeErrorKind::InvalidTypeBitmap { window: 0 }));
+        assert!(matches!(decode(b"\x00\x02\x40").unwrap_err().kind, DecodeErrorKind::RdataOverrun));
+    }
+
+    #[test]
+    fn decodes_and_encodes_typed_data() {
+        let rdata = [&[0, 10][..], &wire("mail.example.com")].concat();
+        let data = decode(Kind::MX, &[], &rdata);
+        let Mx { preference, exchange } = &data else { panic!("{data:?} should be MX") };
+        assert_eq!((*preference, exchange.to_string().as_str()), (10, "mail.example.com."));
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = [
+            wire("ns1.example.com"), wire("hostmaster.example.com"),
+            [2024010101u32, 7200, 3600, 1209600, 300].iter().flat_map(|value| value.to_be_bytes()).collect(),
+        ].concat();
+        let data = decode(Kind::SOA, &[], &rdata);
+        let Soa { mname, rname, serial, refresh, retry, expire, minimum } = &data else { panic!("{data:?} should be SOA") };
+        assert_eq!(mname, &"ns1.example.com".parse::<Name>().unwrap());
+        assert_eq!(rname, &"hostmaster.example.com".parse::<Name>().unwrap());
+        assert_eq!([*serial, *refresh, *retry, *expire, *minimum], [2024010101, 7200, 3600, 1209600, 300]);
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = b"\x0bv=spf1 -all\x00\x03\xff\\\"".to_vec();
+        let data = decode(Kind::TXT, &[], &rdata);
+        let Txt(strings) = &data else { panic!("{data:?} should be TXT") };
+        assert_eq!(strings, &[b"v=spf1 -all".to_vec(), Vec::new(), b"\xff\\\"".to_vec()]);
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = b"\x02PC\x05Linux".to_vec();
+        let data = decode(Kind::HINFO, &[], &rdata);
+        let Hinfo { cpu, os } = &data else { panic!("{data:?} should be HINFO") };
+        assert_eq!((cpu.as_slice(), os.as_slice()), (&b"PC"[..], &b"Linux"[..]));
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = [&[0, 10, 0, 20, 0x13, 0xc4][..], &wire("sip.example.com")].concat();
+        let data = decode(Kind::SRV, &[], &rdata);
+        let Srv { priority, weight, port, target } = &data else { panic!("{data:?} should be SRV") };
+        assert_eq!((*priority, *weight, *port), (10, 20, 5060));
+        assert_eq!(target.to_string(), "sip.example.com.");
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = b"\x80\x05issueletsencrypt.org".to_vec();
+        let data = decode(Kind::CAA, &[], &rdata);
+        let Caa { flags, tag, value } = &data else { panic!("{data:?} should be CAA") };
+        assert_eq!((*flags, tag.as_slice(), value.as_slice()), (0x80, &b"issue"[..], &b"letsencrypt.org"[..]));
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = [&b"\x00\x64\x00\x0a\x01U\x07E2U+sip\x1b!^.*$!sip:info@example.com!"[..], &[0]].concat();
+        let data = decode(Kind::NAPTR, &[], &rdata);
+        let Naptr { order, preference, flags, services, regexp, replacement } = &data else { panic!("{data:?} should be NAPTR") };
+        assert_eq!((*order, *preference), (100, 10));
+        assert_eq!((flags.as_slice(), services.as_slice()), (&b"U"[..], &b"E2U+sip"[..]));
+        assert_eq!(regexp.as_slice(), b"!^.*$!sip:info@example.com!");
+        assert!(replacement.is_root());
+        assert_eq!(encode(&data), rdata);
+    }
+
+    #[test]
+    fn rejects_truncated_typed_data() {
+        let decode = |kind: Kind, rdata: &[u8]| {
+            let mut reader = Reader::new(rdata);
+            reader.with_limit(rdata.len(), |reader| decode_data(reader, kind))
+        };
+        assert!(decode(Kind::MX, &[0]).is_err());
+        assert!(decode(Kind::SRV, &[0, 10, 0, 20, 0x13]).is_err());
+        assert!(decode(Kind::TXT, b"\x05abc").is_err());
+        assert!(decode(Kind::CAA, b"\x00\x05iss").is_err());
+    }
+
+    #[test]
+    fn decodes_compressed_minfo_names() {
+        let prefix = wire("example.com");
+        let rdata = b"\x05owner\xc0\x00\x06errors\xc0\x00";
+        let data = decode(Kind::MINFO, &prefix, rdata);
+        let Minfo { rmailbx, emailbx } = &data else { panic!("{data:?} should be MINFO") };
+        assert_eq!(rmailbx.to_string(), "owner.example.com.");
+        assert_eq!(emailbx.to_string(), "errors.example.com.");
+        assert_eq!(encode(&data), [wire("owner.example.com"), wire("errors.example.com")].concat());
+        assert_eq!(data.to_string(), "owner.example.com. errors.example.com.");
+
+        let record = parse("list.example.com. 300 IN MINFO owner.example.com. errors.example.com.");
+        let Minfo { rmailbx, .. } = &record.data else { panic!("{:?} should be MINFO", record.data) };
+        assert_eq!(rmailbx.to_string(), "owner.example.com.");
+        assert_eq!(wire_round_trip(&record).to_string(), record.to_string());
+    }
+
+    #[test]
+    fn computes_dnskey_key_tags() {
+        // The root zone KSK-2017.
+        let record = parse(concat!(
+            ". 172800 IN DNSKEY 257 3 8 ",
+            "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN",
+            "7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8",
+            "efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLY",
+            "A4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+        ));
+        assert_eq!(record.data.key_tag(), Some(20326));
+        assert_eq!(parse("example.com. 300 IN A 192.0.2.1").data.key_tag(), None);
+    }
+
+    #[test]
+    fn decodes_dnssec_data() {
+        let rdata = [&[0xec, 0x45, 5, 1][..], &[0x2b, 0xb1, 0x83, 0xaf]].concat();
+        let data = decode(Kind::DS, &[], &rdata);
+        let Ds { key_tag, algorithm, digest_type, digest } = &data else { panic!("{data:?} should be DS") };
+        assert_eq!((*key_tag, *algorithm, *digest_type, digest.as_slice()), (60485, 5, 1, &[0x2b, 0xb1, 0x83, 0xaf][..]));
+        assert_eq!(data.to_string(), "60485 5 1 2BB183AF");
+        assert_eq!(encode(&data), rdata);
+
+        let prefix = wire("example.com");
+        let rdata = [
+            &[0, 1, 13, 2, 0, 0, 0x0e, 0x10][..],
+            &1706745600u32.to_be_bytes(), &1704067200u32.to_be_bytes(),
+            &[0x30, 0x39, 0xc0, 0x00], b"sig",
+        ].concat();
+        let data = decode(Kind::RRSIG, &prefix, &rdata);
+        let Rrsig { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature } = &data else {
+            panic!("{data:?} should be RRSIG")
+        };
+        assert_eq!((*type_covered, *algorithm, *labels, *original_ttl), (Kind::A, 13, 2, 3600));
+        assert_eq!((*expiration, *inception, *key_tag), (1706745600, 1704067200, 12345));
+        assert_eq!((signer_name.to_string().as_str(), signature.as_slice()), ("example.com.", &b"sig"[..]));
+        assert_eq!(data.to_string(), "A 13 2 3600 20240201000000 20240101000000 12345 example.com. c2ln");
+
+        let rdata = [&wire("www.example.com")[..], &[0, 6, 0x40, 0x01, 0, 0, 0, 0x03], &[0x04, 1, 0x40]].concat();
+        let data = decode(Kind::NSEC, &[], &rdata);
+        let Nsec { next_domain, types } = &data else { panic!("{data:?} should be NSEC") };
+        assert_eq!(next_domain.to_string(), "www.example.com.");
+        assert_eq!(types, &[Kind::A, Kind::MX, Kind::RRSIG, Kind::NSEC, Kind::from(1025)]);
+        assert_eq!(encode(&data), rdata);
+
+        let rdata = b"\x01\x01\x00\x0a\x02\xaa\xbb\x05\x00\x01\x02\x03\x04\x00\x01\x40".to_vec();
+        let data = decode(Kind::NSEC3, &[], &rdata);
+        let Nsec3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } = &data else {
+            panic!("{data:?} should be NSEC3")
+        };
+        assert_eq!((*hash_algorithm, *flags, *iterations, salt.as_slice()), (1, 1, 10, &[0xaa, 0xbb][..]));
+        assert_eq!((next_hashed_owner.as_slice(), types.as_slice()), (&[0, 1, 2, 3, 4][..], &[Kind::A][..]));
+        assert_eq!(data.to_string(), "1 1 10 AABB 000G40O4 A");
+        assert_eq!(encode(&data), rdata);
+    }
+
+    /// Decodes a record owned by the root with the given type, RDLENGTH and RDATA.
+    fn decode_record(kind: Kind, rdlength: u16, rdata: &[u8]) -> Result<DNSRecord, Error> {
+        let header = [&[0][..], &u16::from(kind).to_be_bytes(), &[0, 1, 0, 0, 0, 0], &rdlength.to_be_bytes()].concat();
+        let bytes = [&header, rdata].concat();
+        let mut reader = Reader::new(&bytes);
+        let record = DNSRecord::try_from(&mut reader)?;
+        assert_eq!(reader.remaining(), 0);
+        Ok(record)
+    }
+
+    #[test]
+    fn rdata_is_bounded_by_rdlength() {
+        let error = decode_record(Kind::A, 5, b"\xc0\x00\x02\x01\x00").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Decode(DecodeError { offset: 11, kind: DecodeErrorKind::RdataLength { kind: Kind::A, expected: 4, actual: 5 } })
+        ));
+        let error = decode_record(Kind::AAAA, 4, b"\xc0\x00\x02\x01").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Decode(DecodeError { kind: DecodeErrorKind::RdataLength { kind: Kind::AAAA, expected: 16, actual: 4 }, .. })
+        ));
+        // The exchange name runs past the RDLENGTH even though the bytes are there.
+        let error = decode_record(Kind::MX, 4, b"\x00\x0a\x04mail\x00").unwrap_err();
+        assert!(matches!(error, Error::Decode(DecodeError { kind: DecodeErrorKind::RdataOverrun, .. })));
+        let error = decode_record(Kind::MX, 5, b"\x00\x0a\x00\xff\xff").unwrap_err();
+        assert!(matches!(error, Error::Decode(DecodeError { offset: 14, kind: DecodeErrorKind::TrailingData { unread: 2 } })));
+        let error = decode_record(Kind::TXT, 10, b"\x03abc").unwrap_err();
+        assert!(matches!(error, Error::Decode(DecodeError { offset: 11, kind: DecodeErrorKind::UnexpectedEof })));
+        assert!(decode_record(Kind::MX, 3, b"\x00\x0a\x00").is_ok());
+    }
+
+    #[test]
+    fn malformed_records_are_errors() {
+        for text in RECORDS {
+            let mut writer = Writer::new();
+            parse(text).encode(&mut writer).unwrap();
+            let bytes = writer.into_bytes();
+            for len in 0..bytes.len() {
+                assert!(DNSRecord::try_from(&mut Reader::new(&bytes[..len])).is_err(), "{text} cut at {len}");
+            }
+            // Corrupted bytes may still decode to something, but never panic.
+            for position in 0..bytes.len() {
+                for value in [0x00, 0x3f, 0xc0, 0xff] {
+                    let mut corrupted = bytes.clone();
+                    corrupted[position] = value;
+                    let _ = DNSRecord::try_from(&mut Reader::new(&corrupted));
+                }
+            }
+        }
+    }
+}
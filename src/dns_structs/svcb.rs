@@ -0,0 +1,391 @@
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use crate::dns_structs::text::{decode_base64, encode_base64, write_character_string};
+use crate::dns_structs::writer::EncodeError;
+
+/// The key of a service parameter, as registered in the "Service Parameter Keys (SvcParamKeys)" IANA registry.
+///
+/// For more information, see [RFC 9460 section 14.3](https://www.rfc-editor.org/rfc/rfc9460#section-14.3).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SvcParamKey {
+    /// Keys that the client must understand to use the record.
+    Mandatory,
+    /// Additional supported protocols.
+    Alpn,
+    /// No support for the default protocol.
+    NoDefaultAlpn,
+    /// The port for alternative endpoints.
+    Port,
+    /// IPv4 address hints.
+    Ipv4Hint,
+    /// Encrypted ClientHello configuration.
+    Ech,
+    /// IPv6 address hints.
+    Ipv6Hint,
+    /// A key not known by this implementation, presented as `keyNNNNN`.
+    Key(u16),
+}
+
+impl From<u16> for SvcParamKey {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => SvcParamKey::Mandatory,
+            1 => SvcParamKey::Alpn,
+            2 => SvcParamKey::NoDefaultAlpn,
+            3 => SvcParamKey::Port,
+            4 => SvcParamKey::Ipv4Hint,
+            5 => SvcParamKey::Ech,
+            6 => SvcParamKey::Ipv6Hint,
+            key => SvcParamKey::Key(key),
+        }
+    }
+}
+
+impl From<SvcParamKey> for u16 {
+    fn from(value: SvcParamKey) -> Self {
+        match value {
+            SvcParamKey::Mandatory => 0,
+            SvcParamKey::Alpn => 1,
+            SvcParamKey::NoDefaultAlpn => 2,
+            SvcParamKey::Port => 3,
+            SvcParamKey::Ipv4Hint => 4,
+            SvcParamKey::Ech => 5,
+            SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::Key(key) => key,
+        }
+    }
+}
+
+impl SvcParamKey {
+    /// Parses the presentation name of a key, either its registered name or `keyNNNNN`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let key = match name.to_ascii_lowercase().as_str() {
+            "mandatory" => SvcParamKey::Mandatory,
+            "alpn" => SvcParamKey::Alpn,
+            "no-default-alpn" => SvcParamKey::NoDefaultAlpn,
+            "port" => SvcParamKey::Port,
+            "ipv4hint" => SvcParamKey::Ipv4Hint,
+            "ech" => SvcParamKey::Ech,
+            "ipv6hint" => SvcParamKey::Ipv6Hint,
+            name => SvcParamKey::from(name.strip_prefix("key")?.parse::<u16>().ok()?),
+        };
+        Some(key)
+    }
+}
+
+impl Display for SvcParamKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SvcParamKey::Mandatory => write!(f, "mandatory"),
+            SvcParamKey::Alpn => write!(f, "alpn"),
+            SvcParamKey::NoDefaultAlpn => write!(f, "no-default-alpn"),
+            SvcParamKey::Port => write!(f, "port"),
+            SvcParamKey::Ipv4Hint => write!(f, "ipv4hint"),
+            SvcParamKey::Ech => write!(f, "ech"),
+            SvcParamKey::Ipv6Hint => write!(f, "ipv6hint"),
+            SvcParamKey::Key(key) => write!(f, "key{}", key),
+        }
+    }
+}
+
+/// A service parameter of an SVCB or HTTPS record.
+///
+/// For more information, see [RFC 9460 section 7](https://www.rfc-editor.org/rfc/rfc9460#section-7).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SvcParam {
+    /// The keys that are mandatory for the record to be usable.
+    Mandatory(Vec<SvcParamKey>),
+    /// The ALPN protocol identifiers supported by the endpoint.
+    Alpn(Vec<Vec<u8>>),
+    /// The default ALPN protocol is not supported by the endpoint.
+    NoDefaultAlpn,
+    /// The TCP or UDP port that should be used to reach the endpoint.
+    Port(u16),
+    /// IPv4 addresses that clients may use to reach the endpoint.
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An ECHConfigList.
+    Ech(Vec<u8>),
+    /// IPv6 addresses that clients may use to reach the endpoint.
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// A parameter with a key not known by this implementation.
+    Unknown {
+        /// The numeric key.
+        key: u16,
+        /// The raw value.
+        value: Vec<u8>,
+    },
+}
+
+impl SvcParam {
+    /// Returns the key of the parameter.
+    pub fn key(&self) -> SvcParamKey {
+        match self {
+            SvcParam::Mandatory(_) => SvcParamKey::Mandatory,
+            SvcParam::Alpn(_) => SvcParamKey::Alpn,
+            SvcParam::NoDefaultAlpn => SvcParamKey::NoDefaultAlpn,
+            SvcParam::Port(_) => SvcParamKey::Port,
+            SvcParam::Ipv4Hint(_) => SvcParamKey::Ipv4Hint,
+            SvcParam::Ech(_) => SvcParamKey::Ech,
+            SvcParam::Ipv6Hint(_) => SvcParamKey::Ipv6Hint,
+            SvcParam::Unknown { key, .. } => SvcParamKey::Key(*key),
+        }
+    }
+
+    /// Decodes the wire format value of the parameter with the given key.
+    ///
+    /// Returns `None` if the value is not valid for the key.
+    pub fn decode(key: u16, value: &[u8]) -> Option<Self> {
+        let param = match SvcParamKey::from(key) {
+            SvcParamKey::Mandatory if !value.is_empty() && value.len() % 2 == 0 => {
+                let keys: Vec<u16> = value.chunks_exact(2).map(|key| u16::from_be_bytes([key[0], key[1]])).collect();
+                // The keys are in strictly increasing order and never include mandatory itself (RFC 9460 section 8).
+                if keys[0] == 0 || keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    return None;
+                }
+                SvcParam::Mandatory(keys.into_iter().map(SvcParamKey::from).collect())
+            }
+            SvcParamKey::Alpn => {
+                let mut ids = Vec::new();
+                let mut rest = value;
+                while let [len, tail @ ..] = rest {
+                    let len = *len as usize;
+                    if len == 0 || len > tail.len() {
+                        return None;
+                    }
+                    ids.push(tail[..len].to_vec());
+                    rest = &tail[len..];
+                }
+                if ids.is_empty() {
+                    return None;
+                }
+                SvcParam::Alpn(ids)
+            }
+            SvcParamKey::NoDefaultAlpn if value.is_empty() => SvcParam::NoDefaultAlpn,
+            SvcParamKey::Port if value.len() == 2 => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+            SvcParamKey::Ipv4Hint if !value.is_empty() && value.len() % 4 == 0 => SvcParam::Ipv4Hint(
+                value.chunks_exact(4).map(|ip| Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])).collect()
+            ),
+            SvcParamKey::Ech => SvcParam::Ech(value.to_vec()),
+            SvcParamKey::Ipv6Hint if !value.is_empty() && value.len() % 16 == 0 => SvcParam::Ipv6Hint(
+                value.chunks_exact(16).map(|ip| Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap_or_default())).collect()
+            ),
+            SvcParamKey::Key(key) => SvcParam::Unknown { key, value: value.to_vec() },
+            _ => return None,
+        };
+        Some(param)
+    }
+
+    /// Parses a parameter in presentation format (RFC 9460 section 2.1) from its key and its value,
+    /// already unescaped as a `<character-string>`.
+    ///
+    /// Returns `None` if the key is unknown or the value is not valid for the key.
+    pub fn parse(key: &str, value: Option<&[u8]>) -> Option<Self> {
+        let key = SvcParamKey::parse(key)?;
+        let Some(value) = value else {
+            return match key {
+                SvcParamKey::NoDefaultAlpn => Some(SvcParam::NoDefaultAlpn),
+                SvcParamKey::Key(key) => Some(SvcParam::Unknown { key, value: Vec::new() }),
+                _ => None,
+            };
+        };
+        let text = std::str::from_utf8(value).ok();
+        let list = || text.map(|text| text.split(','));
+        let param = match key {
+            SvcParamKey::Mandatory => {
+                let mut keys: Vec<u16> = list()?.map(|key| SvcParamKey::parse(key).map(u16::from)).collect::<Option<_>>()?;
+                // The keys may come in any order but are sent sorted, and neither repeat nor include mandatory itself.
+                keys.sort_unstable();
+                if keys[0] == 0 || keys.windows(2).any(|pair| pair[0] == pair[1]) {
+                    return None;
+                }
+                SvcParam::Mandatory(keys.into_iter().map(SvcParamKey::from).collect())
+            }
+            SvcParamKey::Alpn => SvcParam::Alpn(split_value_list(value)?),
+            SvcParamKey::NoDefaultAlpn => return None,
+            SvcParamKey::Port => SvcParam::Port(text?.parse().ok()?),
+            SvcParamKey::Ipv4Hint => SvcParam::Ipv4Hint(list()?.map(|ip| ip.parse().ok()).collect::<Option<_>>()?),
+            SvcParamKey::Ech => SvcParam::Ech(decode_base64(text?)?),
+            SvcParamKey::Ipv6Hint => SvcParam::Ipv6Hint(list()?.map(|ip| ip.parse().ok()).collect::<Option<_>>()?),
+            SvcParamKey::Key(key) => SvcParam::Unknown { key, value: value.to_vec() },
+        };
+        Some(param)
+    }
+
+    /// Encodes the value of the parameter in wire format.
+    pub fn encode_value(&self) -> Result<Vec<u8>, EncodeError> {
+        let value = match self {
+            SvcParam::Mandatory(keys) => keys.iter().flat_map(|key| u16::from(*key).to_be_bytes()).collect(),
+            SvcParam::Alpn(ids) => {
+                let mut value = Vec::new();
+                for id in ids {
+                    let len = u8::try_from(id.len()).map_err(|_| EncodeError::CharacterStringTooLong(id.len()))?;
+                    value.push(len);
+                    value.extend_from_slice(id);
+                }
+                value
+            }
+            SvcParam::NoDefaultAlpn => Vec::new(),
+            SvcParam::Port(port) => port.to_be_bytes().to_vec(),
+            SvcParam::Ipv4Hint(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+            SvcParam::Ech(config) => config.clone(),
+            SvcParam::Ipv6Hint(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+            SvcParam::Unknown { value, .. } => value.clone(),
+        };
+        Ok(value)
+    }
+}
+
+/// Splits a comma separated list where commas and backslashes inside the items are escaped with a
+/// backslash (RFC 9460 appendix A.1). Returns `None` for empty items or a trailing backslash.
+fn split_value_list(value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut items = vec![Vec::new()];
+    let mut bytes = value.iter();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => items.last_mut()?.push(*bytes.next()?),
+            b',' => items.push(Vec::new()),
+            byte => items.last_mut()?.push(byte),
+        }
+    }
+    if items.iter().any(Vec::is_empty) {
+        return None;
+    }
+    Some(items)
+}
+
+/// Writes a comma separated list, escaping commas and backslashes inside the items (RFC 9460 appendix A.1).
+fn write_value_list(f: &mut Formatter<'_>, items: &[Vec<u8>]) -> std::fmt::Result {
+    let mut value = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            value.push(b',');
+        }
+        for &byte in item {
+            if byte == b',' || byte == b'\\' {
+                value.push(b'\\');
+            }
+            value.push(byte);
+        }
+    }
+    write_character_string(f, &value)
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key())?;
+        match self {
+            SvcParam::Mandatory(keys) => {
+                write!(f, "=")?;
+                for (i, key) in keys.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { "," } else { "" }, key)?;
+                }
+                Ok(())
+            }
+            SvcParam::Alpn(ids) => {
+                write!(f, "=")?;
+                write_value_list(f, ids)
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(ips) => {
+                write!(f, "=")?;
+                for (i, ip) in ips.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { "," } else { "" }, ip)?;
+                }
+                Ok(())
+            }
+            SvcParam::Ech(config) => write!(f, "={}", encode_base64(config)),
+            SvcParam::Ipv6Hint(ips) => {
+                write!(f, "=")?;
+                for (i, ip) in ips.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { "," } else { "" }, ip)?;
+                }
+                Ok(())
+            }
+            SvcParam::Unknown { value, .. } if value.is_empty() => Ok(()),
+            SvcParam::Unknown { value, .. } => {
+                write!(f, "=")?;
+                write_character_string(f, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns_structs::reader::{DecodeError, DecodeErrorKind, Reader};
+    use crate::dns_structs::record::{decode_data, Data};
+    use crate::dns_structs::text::decode_hex;
+    use crate::dns_structs::writer::Writer;
+    use crate::Kind;
+
+    fn decode(hex: &str) -> Result<Data, DecodeError> {
+        let rdata = decode_hex(&hex.replace(' ', "")).unwrap();
+        Reader::new(&rdata).with_limit(rdata.len(), |reader| decode_data(reader, Kind::SVCB))
+    }
+
+    /// Decodes `hex`, checks its presentation and that it encodes back to the same bytes.
+    fn check(hex: &str, text: &str) {
+        let data = decode(hex).unwrap_or_else(|error| panic!("{hex} should decode: {error}"));
+        assert_eq!(data.to_string(), text);
+        let mut writer = Writer::new();
+        data.encode(&mut writer, false).unwrap();
+        assert_eq!(writer.into_bytes(), decode_hex(&hex.replace(' ', "")).unwrap());
+    }
+
+    #[test]
+    fn decodes_rfc_9460_vectors() {
+        // RFC 9460 appendix D, figures 2 to 10.
+        check("0000 03666f6f076578616d706c6503636f6d00", "0 foo.example.com.");
+        check("0001 00", "1 .");
+        check("0010 03666f6f076578616d706c6503636f6d00 0003 0002 0035", "16 foo.example.com. port=53");
+        check("0001 03666f6f076578616d706c6503636f6d00 029b 0005 68656c6c6f", "1 foo.example.com. key667=\"hello\"");
+        check("0001 03666f6f076578616d706c6503636f6d00 029b 0009 68656c6c6fd2716f6f", "1 foo.example.com. key667=\"hello\\210qoo\"");
+        check(
+            "0001 03666f6f076578616d706c6503636f6d00 0006 0020 20010db8000000000000000000000001 20010db8000000000000000000530001",
+            "1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1",
+        );
+        check(
+            "0001 076578616d706c6503636f6d00 0006 0010 20010db8012203440000 0000c0000221",
+            "1 example.com. ipv6hint=2001:db8:122:344::c000:221",
+        );
+        check(
+            "0010 03666f6f076578616d706c65036f726700 0000 0004 00010004 0001 0009 026832 0568332d3139 0004 0004 c0000201",
+            "16 foo.example.org. mandatory=alpn,ipv4hint alpn=\"h2,h3-19\" ipv4hint=192.0.2.1",
+        );
+        check(
+            "0010 03666f6f076578616d706c65036f726700 0001 000c 08665c6f6f2c626172 026832",
+            r#"16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#,
+        );
+        check("0001 00 0002 0000 0005 0003 0045fe", "1 . no-default-alpn ech=AEX+");
+        check("0001 00 fde9 0000", "1 . key65001");
+    }
+
+    #[test]
+    fn rejects_unsorted_and_duplicate_keys() {
+        let error = decode("0001 00 0003 0002 0035 0001 0003 026832").unwrap_err();
+        assert!(matches!(error.kind, DecodeErrorKind::UnsortedSvcParams { key: 1 }));
+        assert_eq!(error.offset, 9);
+        let error = decode("0001 00 0003 0002 0035 0003 0002 0050").unwrap_err();
+        assert!(matches!(error.kind, DecodeErrorKind::UnsortedSvcParams { key: 3 }));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for (hex, key) in [
+            ("0001 00 0000 0004 00040001", 0),
+            ("0001 00 0000 0004 00010001", 0),
+            ("0001 00 0000 0002 0000", 0),
+            ("0001 00 0001 0000", 1),
+            ("0001 00 0001 0003 036832", 1),
+            ("0001 00 0002 0001 00", 2),
+            ("0001 00 0003 0001 35", 3),
+            ("0001 00 0004 0003 c00002", 4),
+            ("0001 00 0006 0004 20010db8", 6),
+        ] {
+            let error = decode(hex).unwrap_err();
+            assert!(matches!(error.kind, DecodeErrorKind::InvalidSvcParam { key: k } if k == key), "{hex}: {error}");
+        }
+        assert!(matches!(decode("0001 00 0003 0004 0035").unwrap_err().kind, DecodeErrorKind::RdataOverrun));
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt::{Formatter, Result, Write};
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE32HEX_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// BIND splits long base64 and hexadecimal fields into space separated chunks of this many characters.
+const CHUNK_SIZE: usize = 56;
+
+/// Writes a `<character-string>` in master-file format: quoted, with `"` and `\` escaped and
+/// non-printable octets written as `\DDD`.
+pub fn write_character_string(f: &mut Formatter<'_>, bytes: &[u8]) -> Result {
+    f.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+            0x20..=0x7e => f.write_char(byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Writes `text` splitting it in chunks of `CHUNK_SIZE` characters, the same way `dig` does.
+fn write_chunked(f: &mut Formatter<'_>, text: &str) -> Result {
+    for (i, chunk) in text.as_bytes().chunks(CHUNK_SIZE).enumerate() {
+        if i > 0 {
+            f.write_char(' ')?;
+        }
+        // Every chunk comes from an ASCII only string.
+        f.write_str(std::str::from_utf8(chunk).unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// Writes the given bytes as uppercase hexadecimal digits, as used by SSHFP, TLSA and DS records.
+pub fn write_hex(f: &mut Formatter<'_>, bytes: &[u8]) -> Result {
+    write_chunked(f, &bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>())
+}
+
+/// Encodes the given bytes as base64 (RFC 4648 section 4).
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Writes the given bytes as base64, as used by DNSKEY and RRSIG records.
+pub fn write_base64(f: &mut Formatter<'_>, bytes: &[u8]) -> Result {
+    write_chunked(f, &encode_base64(bytes))
+}
+
+/// Writes the given bytes as unpadded base32 with the extended hex alphabet (RFC 4648 section 7),
+/// as used by the NSEC3 next hashed owner name.
+pub fn write_base32hex(f: &mut Formatter<'_>, bytes: &[u8]) -> Result {
+    for chunk in bytes.chunks(5) {
+        let group = chunk.iter().enumerate().fold(0u64, |acc, (i, byte)| acc | (*byte as u64) << (32 - 8 * i));
+        let digits = (chunk.len() * 8).div_ceil(5);
+        for i in 0..digits {
+            f.write_char(BASE32HEX_ALPHABET[(group >> (35 - 5 * i) & 0x1f) as usize] as char)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a DNSSEC timestamp (seconds since the epoch) as `YYYYMMDDHHmmSS` in UTC.
+pub fn write_timestamp(f: &mut Formatter<'_>, timestamp: u32) -> Result {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    write!(f, "{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, seconds / 3_600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Parses the text of a `<character-string>` in master-file format, without its quotes: `\DDD`
+/// stands for the octet with that decimal value and `\X` for `X`.
+///
+/// Returns `None` for a trailing backslash or a `\DDD` above 255.
+pub fn parse_character_string(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut string = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            string.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1..i + 4) {
+            Some(digits) if digits.iter().all(u8::is_ascii_digit) => {
+                let value = digits.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as u16);
+                string.push(u8::try_from(value).ok()?);
+                i += 4;
+            }
+            _ => {
+                string.push(*bytes.get(i + 1)?);
+                i += 2;
+            }
+        }
+    }
+    Some(string)
+}
+
+/// Decodes hexadecimal digits in either case. Returns `None` for an odd number of digits or any other character.
+pub fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Decodes text written with an alphabet of `2^bits` symbols, most significant bits first.
+fn decode_bits(text: &[u8], bits: u32, value_of: impl Fn(u8) -> Option<u8>) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * bits as usize / 8);
+    let mut group = 0u32;
+    let mut pending = 0;
+    for &symbol in text {
+        group = group << bits | value_of(symbol)? as u32;
+        pending += bits;
+        if pending >= 8 {
+            pending -= 8;
+            bytes.push((group >> pending) as u8);
+            group &= (1 << pending) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Decodes base64 (RFC 4648 section 4); the padding is optional.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    decode_bits(text.as_bytes(), 6, |symbol| BASE64_ALPHABET.iter().position(|&c| c == symbol).map(|value| value as u8))
+}
+
+/// Decodes unpadded base32 with the extended hex alphabet (RFC 4648 section 7), in either case.
+pub fn decode_base32hex(text: &str) -> Option<Vec<u8>> {
+    if matches!(text.len() % 8, 1 | 3 | 6) {
+        return None;
+    }
+    decode_bits(text.as_bytes(), 5, |symbol| {
+        BASE32HEX_ALPHABET.iter().position(|&c| c == symbol.to_ascii_uppercase()).map(|value| value as u8)
+    })
+}
+
+/// Parses a DNSSEC timestamp, either `YYYYMMDDHHmmSS` in UTC or a number of seconds since the epoch
+/// (RFC 4034 section 3.2).
+pub fn parse_timestamp(text: &str) -> Option<u32> {
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    if text.len() != 14 {
+        return text.parse().ok();
+    }
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    // Days from civil, the inverse of `write_timestamp`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u32::try_from(days * 86_400 + hour * 3_600 + minute * 60 + second).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders the output of one of the `write_*` functions.
+    fn render(write: impl Fn(&mut Formatter<'_>) -> Result) -> String {
+        struct Show<F>(F);
+        impl<F: Fn(&mut Formatter<'_>) -> Result> std::fmt::Display for Show<F> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+                (self.0)(f)
+            }
+        }
+        Show(write).to_string()
+    }
+
+    #[test]
+    fn base64_follows_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, text) in vectors {
+            assert_eq!(encode_base64(bytes.as_bytes()), text);
+            assert_eq!(decode_base64(text).as_deref(), Some(bytes.as_bytes()));
+            assert_eq!(decode_base64(text.trim_end_matches('=')).as_deref(), Some(bytes.as_bytes()));
+        }
+        assert_eq!(decode_base64("Zm9vY"), None);
+        assert_eq!(decode_base64("Zm9*"), None);
+    }
+
+    #[test]
+    fn base32hex_follows_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""), ("f", "CO"), ("fo", "CPNG"), ("foo", "CPNMU"), ("foob", "CPNMUOG"), ("fooba", "CPNMUOJ1"), ("foobar", "CPNMUOJ1E8"),
+        ];
+        for (bytes, text) in vectors {
+            assert_eq!(render(|f| write_base32hex(f, bytes.as_bytes())), text);
+            assert_eq!(decode_base32hex(&text.to_lowercase()).as_deref(), Some(bytes.as_bytes()));
+        }
+        assert_eq!(decode_base32hex("CPN"), None);
+        assert_eq!(decode_base32hex("CPNW"), None);
+    }
+
+    #[test]
+    fn hex_needs_an_even_number_of_digits() {
+        assert_eq!(decode_hex("00aBff").as_deref(), Some(&[0x00, 0xab, 0xff][..]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(render(|f| write_hex(f, &[0xab; 30])), format!("{} {}", "AB".repeat(28), "AB".repeat(2)));
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        for (text, seconds) in [("19700101000000", 0), ("20240229235959", 1709251199), ("21060207062815", u32::MAX)] {
+            assert_eq!(render(|f| write_timestamp(f, seconds)), text);
+            assert_eq!(parse_timestamp(text), Some(seconds));
+        }
+        assert_eq!(parse_timestamp("1704067200"), Some(1704067200));
+        assert_eq!(parse_timestamp("20241301000000"), None);
+        assert_eq!(parse_timestamp("2024-01-01"), None);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::fmt::{Display, Formatter};
+use crate::dns_structs::name::Name;
+
+/// Pointers can only address the first 16 KiB of a message, the remaining two bits mark the pointer itself.
+const MAX_POINTER: usize = 0x3fff;
+
+/// An error found while encoding a message.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// A `<character-string>` is longer than 255 octets.
+    CharacterStringTooLong(usize),
+    /// The data of a record or of a service parameter is longer than 65535 octets.
+    DataTooLong(usize),
+    /// A section holds more than 65535 entries.
+    TooManyRecords(usize),
+    /// The buffer given to a `SliceWriter` cannot hold the message; holds the size of the buffer.
+    BufferTooSmall(usize),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::CharacterStringTooLong(len) => write!(f, "character string too long ({} bytes)", len),
+            EncodeError::DataTooLong(len) => write!(f, "data too long ({} bytes)", len),
+            EncodeError::TooManyRecords(len) => write!(f, "too many records in a section ({})", len),
+            EncodeError::BufferTooSmall(len) => write!(f, "the message does not fit in a buffer of {} bytes", len),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// A buffer that serializes DNS messages applying the name compression scheme of RFC 1035 section 4.1.4.
+#[derive(Default)]
+pub struct Writer {
+    buffer: Vec<u8>,
+    /// Offsets of the names already written, by the hash of their uncompressed wire format in
+    /// lowercase. Only the first name with a given hash is kept.
+    names: HashMap<u64, u16>,
+    hasher: RandomState,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Extends the buffer by `len` zero bytes and returns a `SliceWriter` over them, for the encoders
+    /// that write into caller-provided buffers.
+    pub fn reserve_slice(&mut self, len: usize) -> SliceWriter<'_> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + len, 0);
+        SliceWriter::new(&mut self.buffer[start..])
+    }
+
+    /// Overwrites a big endian `u16` previously written at `offset`.
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.buffer[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// Writes a `<character-string>`: a single length octet followed by at most 255 octets.
+    pub fn write_character_string(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let len = u8::try_from(bytes.len()).map_err(|_| EncodeError::CharacterStringTooLong(bytes.len()))?;
+        self.write_u8(len);
+        self.write_bytes(bytes);
+        Ok(())
+    }
+
+    /// Writes a domain name in wire format.
+    ///
+    /// When `compress` is true, the longest suffix of `name` that has already been written is
+    /// replaced by a pointer to it. Names written with `compress` set to false are still
+    /// remembered, so later names can point to them.
+    pub fn write_name(&mut self, name: &Name, compress: bool) {
+        let wire = name.wire();
+        for label in name.label_offsets() {
+            let suffix = &wire[label..];
+            let hash = self.hash(suffix);
+            let known = self.names.get(&hash).copied().filter(|&offset| self.is_name_at(offset as usize, suffix));
+            if let Some(offset) = known.filter(|_| compress) {
+                self.write_u16(0xc000 | offset);
+                return;
+            }
+            let offset = self.len();
+            if offset <= MAX_POINTER {
+                self.names.entry(hash).or_insert(offset as u16);
+            }
+            self.write_bytes(&wire[label..label + 1 + wire[label] as usize]);
+        }
+        self.write_u8(0);
+    }
+
+    /// Hashes an uncompressed name in lowercase, without copying it.
+    fn hash(&self, wire: &[u8]) -> u64 {
+        let mut hasher = self.hasher.build_hasher();
+        for byte in wire {
+            hasher.write_u8(byte.to_ascii_lowercase());
+        }
+        hasher.finish()
+    }
+
+    /// Returns whether the name written at `offset` is `wire`, an uncompressed name, ignoring ASCII case.
+    fn is_name_at(&self, mut offset: usize, mut wire: &[u8]) -> bool {
+        loop {
+            let len = self.buffer[offset] as usize;
+            if len & 0xc0 == 0xc0 {
+                // Every pointer was written by `write_name` and points back to an earlier name.
+                offset = (len & 0x3f) << 8 | self.buffer[offset + 1] as usize;
+                continue;
+            }
+            // Length octets are below 64, so they are never changed by the ASCII case folding.
+            let label = &self.buffer[offset..offset + 1 + len];
+            match wire.split_at_checked(label.len()) {
+                Some((head, tail)) if head.eq_ignore_ascii_case(label) => wire = tail,
+                _ => return false,
+            }
+            if len == 0 {
+                return wire.is_empty();
+            }
+            offset += 1 + len;
+        }
+    }
+}
+
+/// A writer over a caller-provided buffer, used to encode messages without allocating.
+///
+/// Every write is bounds checked and fails with `EncodeError::BufferTooSmall` once the buffer is
+/// full. Names are written uncompressed, which is all a query needs.
+pub struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, position: 0 }
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == 0
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let end = self.position + bytes.len();
+        let capacity = self.buffer.len();
+        let target = self.buffer.get_mut(self.position..end).ok_or(EncodeError::BufferTooSmall(capacity))?;
+        target.copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<(), EncodeError> {
+        self.write_bytes(&[value])
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> Result<(), EncodeError> {
+        self.write_bytes(&value.to_be_bytes())
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> Result<(), EncodeError> {
+        self.write_bytes(&value.to_be_bytes())
+    }
+
+    /// Writes a domain name in uncompressed wire format.
+    pub fn write_name(&mut self, name: &Name) -> Result<(), EncodeError> {
+        self.write_bytes(name.wire())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_structs::reader::{NameDecoder, Reader};
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    /// Writes `names` one after the other and checks that they decode back from the output.
+    fn round_trip(names: &[&str], compress: bool) -> Vec<u8> {
+        let mut writer = Writer::new();
+        for text in names {
+            writer.write_name(&name(text), compress);
+        }
+        let bytes = writer.into_bytes();
+        let mut reader = Reader::new(&bytes);
+        for text in names {
+            assert_eq!(reader.decode_name().unwrap(), name(text));
+        }
+        assert_eq!(reader.remaining(), 0);
+        bytes
+    }
+
+    #[test]
+    fn compresses_repeated_suffixes() {
+        let bytes = round_trip(&["example.com.", "www.example.com.", "mail.www.example.com.", "example.com."], true);
+        assert_eq!(
+            bytes,
+            b"\x07example\x03com\x00\x03www\xc0\x00\x04mail\xc0\x0d\xc0\x00"
+        );
+    }
+
+    #[test]
+    fn compresses_ignoring_case() {
+        let bytes = round_trip(&["Example.COM.", "www.example.com."], true);
+        assert_eq!(bytes, b"\x07Example\x03COM\x00\x03www\xc0\x00");
+    }
+
+    #[test]
+    fn writes_root_and_unrelated_names() {
+        let bytes = round_trip(&[".", "example.com.", "example.net.", "."], true);
+        assert_eq!(bytes, b"\x00\x07example\x03com\x00\x07example\x03net\x00\x00");
+    }
+
+    #[test]
+    fn uncompressed_names_can_be_pointed_to() {
+        let mut writer = Writer::new();
+        writer.write_name(&name("example.com."), false);
+        writer.write_name(&name("example.com."), false);
+        writer.write_name(&name("www.example.com."), true);
+        assert_eq!(
+            writer.into_bytes(),
+            b"\x07example\x03com\x00\x07example\x03com\x00\x03www\xc0\x00"
+        );
+    }
+
+    #[test]
+    fn does_not_point_past_16_kib() {
+        let mut writer = Writer::new();
+        writer.write_bytes(&[0; MAX_POINTER + 1]);
+        writer.write_name(&name("example.com."), true);
+        writer.write_name(&name("example.com."), true);
+        let bytes = writer.into_bytes();
+        assert_eq!(&bytes[MAX_POINTER + 1..], b"\x07example\x03com\x00\x07example\x03com\x00");
+    }
+
+    #[test]
+    fn slice_writer_fails_when_full() {
+        let mut buffer = [0; 12];
+        let mut writer = SliceWriter::new(&mut buffer);
+        assert!(matches!(writer.write_name(&name("example.com.")), Err(EncodeError::BufferTooSmall(12))));
+        writer.write_name(&name("example.")).unwrap();
+        assert_eq!(writer.len(), 9);
+    }
+
+    #[test]
+    fn reserved_slices_are_bounded() {
+        let mut writer = Writer::new();
+        writer.write_u8(1);
+        let mut slice = writer.reserve_slice(3);
+        slice.write_u16(0x0203).unwrap();
+        assert!(matches!(slice.write_u16(0x0405), Err(EncodeError::BufferTooSmall(3))));
+        writer.write_u8(5);
+        assert_eq!(writer.into_bytes(), [1, 2, 3, 0, 5]);
+    }
+}
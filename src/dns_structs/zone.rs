@@ -0,0 +1,1000 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::dns_structs::name::{Name, NameParseError};
+use crate::dns_structs::reader::Reader;
+use crate::dns_structs::record::{decode_data, Data, DNSRecord};
+use crate::dns_structs::svcb::SvcParam;
+use crate::dns_structs::text::{decode_base32hex, decode_base64, decode_hex, parse_character_string, parse_timestamp};
+use crate::enums::Class;
+use crate::Kind;
+
+/// How many `$INCLUDE` directives can be nested, which also stops files that include themselves.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// How many records a single `$GENERATE` directive can produce.
+const MAX_GENERATE_RECORDS: u64 = 65536;
+
+/// The widest field a `${offset,width,base}` modifier can ask for, which is as long as a name or a
+/// `<character-string>` can be.
+const MAX_GENERATE_WIDTH: usize = 255;
+
+/// An error found while parsing a zone file, along with the line where it was found.
+#[derive(Debug)]
+pub struct ZoneError {
+    /// The line of the file where the entry with the error starts, counting from 1.
+    pub line: usize,
+    /// What went wrong.
+    pub kind: ZoneErrorKind,
+}
+
+impl ZoneError {
+    pub fn new(line: usize, kind: ZoneErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+#[derive(Debug)]
+pub enum ZoneErrorKind {
+    /// A zone file could not be read.
+    Io(io::Error),
+    /// A `)` has no matching `(`, or the file ends inside parentheses.
+    UnbalancedParentheses,
+    /// The file ends inside a quoted string.
+    UnterminatedString,
+    /// A `$` directive that is not `$ORIGIN`, `$TTL`, `$INCLUDE` or `$GENERATE`.
+    UnknownDirective(String),
+    /// A record starts with blank space but there is no previous owner to repeat.
+    MissingOwner,
+    /// A record has no TTL and there is no `$TTL` or previous record to take it from.
+    MissingTtl,
+    /// The type of a record is missing or not known.
+    UnknownType(String),
+    /// A domain name is not valid.
+    InvalidName {
+        /// The name as written in the file.
+        name: String,
+        /// Why it is not valid.
+        error: NameParseError,
+    },
+    /// A field of a directive or of the data of a record is missing.
+    MissingField(&'static str),
+    /// A field of a directive or of the data of a record is not valid.
+    InvalidField {
+        /// What the field holds.
+        field: &'static str,
+        /// The field as written in the file.
+        value: String,
+    },
+    /// The data of a record has more fields than its type takes.
+    TrailingField(String),
+    /// The record type has no presentation format known by this implementation, so its data must
+    /// be written in the generic `\#` format of RFC 3597.
+    GenericDataRequired(Kind),
+    /// The generic `\#` data does not match its length or is not valid for the record type.
+    InvalidGenericData(String),
+    /// Too many nested `$INCLUDE` directives.
+    IncludeTooDeep,
+    /// A `$GENERATE` range produces more records than allowed; holds the number of records.
+    GenerateRangeTooLarge(u64),
+    /// A `${offset,width,base}` modifier of `$GENERATE` asks for a field wider than allowed; holds the width.
+    GenerateWidthTooLarge(usize),
+    /// An error found in an included file.
+    Include {
+        /// The included file.
+        path: PathBuf,
+        /// The error, with the line in the included file.
+        error: Box<ZoneError>,
+    },
+}
+
+impl Display for ZoneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for ZoneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.kind.source()
+    }
+}
+
+impl std::error::Error for ZoneErrorKind {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZoneErrorKind::Io(err) => Some(err),
+            ZoneErrorKind::InvalidName { error, .. } => Some(error),
+            ZoneErrorKind::Include { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ZoneErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoneErrorKind::Io(err) => write!(f, "cannot read the zone file: {}", err),
+            ZoneErrorKind::UnbalancedParentheses => write!(f, "unbalanced parentheses"),
+            ZoneErrorKind::UnterminatedString => write!(f, "unterminated quoted string"),
+            ZoneErrorKind::UnknownDirective(directive) => write!(f, "unknown directive {}", directive),
+            ZoneErrorKind::MissingOwner => write!(f, "no previous owner name to repeat"),
+            ZoneErrorKind::MissingTtl => write!(f, "no TTL and no $TTL or previous TTL to use"),
+            ZoneErrorKind::UnknownType(kind) => write!(f, "unknown record type {:?}", kind),
+            ZoneErrorKind::InvalidName { name, error } => write!(f, "invalid name {:?}: {}", name, error),
+            ZoneErrorKind::MissingField(field) => write!(f, "missing {}", field),
+            ZoneErrorKind::InvalidField { field, value } => write!(f, "invalid {} {:?}", field, value),
+            ZoneErrorKind::TrailingField(value) => write!(f, "unexpected field {:?}", value),
+            ZoneErrorKind::GenericDataRequired(kind) => write!(f, "{} data must use the generic \\# format", kind),
+            ZoneErrorKind::InvalidGenericData(reason) => write!(f, "invalid generic data: {}", reason),
+            ZoneErrorKind::IncludeTooDeep => write!(f, "too many nested $INCLUDE directives"),
+            ZoneErrorKind::GenerateRangeTooLarge(count) =>
+                write!(f, "$GENERATE range of {} records exceeds {}", count, MAX_GENERATE_RECORDS),
+            ZoneErrorKind::GenerateWidthTooLarge(width) =>
+                write!(f, "$GENERATE width {} exceeds {}", width, MAX_GENERATE_WIDTH),
+            ZoneErrorKind::Include { path, error } => write!(f, "in {}, {}", path.display(), error),
+        }
+    }
+}
+
+/// Parses a zone in master-file format (RFC 1035 section 5) and returns its records in order.
+///
+/// Relative names are completed with `origin` until a `$ORIGIN` directive changes it. Besides
+/// `$ORIGIN`, the `$TTL` (RFC 2308), `$INCLUDE` and BIND's `$GENERATE` directives are supported;
+/// included files are looked up relative to the current directory.
+pub fn parse_zone(text: &str, origin: &Name) -> Result<Vec<DNSRecord>, ZoneError> {
+    let mut parser = Parser::new(origin.clone(), PathBuf::new(), 0);
+    parser.parse(text)?;
+    Ok(parser.records)
+}
+
+/// Reads and parses a zone file, see `parse_zone`. Included files are looked up relative to the
+/// directory of the file that includes them.
+pub fn parse_zone_file(path: impl AsRef<Path>, origin: &Name) -> Result<Vec<DNSRecord>, ZoneError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|err| ZoneError::new(0, ZoneErrorKind::Io(err)))?;
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut parser = Parser::new(origin.clone(), directory, 0);
+    parser.parse(&text)?;
+    Ok(parser.records)
+}
+
+/// Parses a single record written on one line or with parentheses, with fully qualified names and
+/// an explicit TTL, as `DNSRecord::from_str` does.
+pub(crate) fn parse_record(text: &str) -> Result<DNSRecord, ZoneErrorKind> {
+    let mut entries = tokenize(text, 1).map_err(|error| error.kind)?.into_iter();
+    let entry = entries.next().ok_or(ZoneErrorKind::MissingOwner)?;
+    if let Some(extra) = entries.next() {
+        return Err(ZoneErrorKind::TrailingField(extra.tokens[0].text.clone()));
+    }
+    let mut parser = Parser::new(Name::root(), PathBuf::new(), 0);
+    parser.parse_record(&entry)?;
+    Ok(parser.records.remove(0))
+}
+
+/// A field of an entry, as written in the file.
+struct Token {
+    /// The text of the field, without quotes and with its escape sequences untouched.
+    text: String,
+    /// Whether the field was a quoted string.
+    quoted: bool,
+}
+
+/// A logical line of the file: a physical line, or several when parentheses are open.
+struct Entry {
+    /// The line where the entry starts.
+    line: usize,
+    /// Whether the entry starts with blank space, meaning that the previous owner applies.
+    blank_start: bool,
+    tokens: Vec<Token>,
+}
+
+/// Splits the text into entries, removing comments and joining the lines inside parentheses.
+fn tokenize(text: &str, first_line: usize) -> Result<Vec<Entry>, ZoneError> {
+    let mut entries = Vec::new();
+    let mut line = first_line;
+    let mut entry = Entry { line, blank_start: false, tokens: Vec::new() };
+    let mut token: Option<String> = None;
+    let mut depth = 0;
+    let mut at_line_start = true;
+    let mut chars = text.chars();
+    let flush = |token: &mut Option<String>, entry: &mut Entry| {
+        if let Some(text) = token.take() {
+            entry.tokens.push(Token { text, quoted: false });
+        }
+    };
+    while let Some(c) = chars.next() {
+        if at_line_start && depth == 0 {
+            entry = Entry { line, blank_start: c == ' ' || c == '\t', tokens: Vec::new() };
+        }
+        at_line_start = false;
+        match c {
+            '\n' => {
+                flush(&mut token, &mut entry);
+                line += 1;
+                at_line_start = true;
+                if depth == 0 && !entry.tokens.is_empty() {
+                    entries.push(std::mem::replace(&mut entry, Entry { line, blank_start: false, tokens: Vec::new() }));
+                }
+            }
+            ' ' | '\t' | '\r' => flush(&mut token, &mut entry),
+            ';' => {
+                flush(&mut token, &mut entry);
+                // Leave the newline so it still ends the entry.
+                let rest = chars.as_str();
+                chars = rest[rest.find('\n').unwrap_or(rest.len())..].chars();
+            }
+            '(' => {
+                flush(&mut token, &mut entry);
+                depth += 1;
+            }
+            ')' => {
+                flush(&mut token, &mut entry);
+                if depth == 0 {
+                    return Err(ZoneError::new(line, ZoneErrorKind::UnbalancedParentheses));
+                }
+                depth -= 1;
+            }
+            '"' => {
+                flush(&mut token, &mut entry);
+                let start = line;
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(ZoneError::new(start, ZoneErrorKind::UnterminatedString)),
+                        Some('"') => break,
+                        Some('\\') => {
+                            text.push('\\');
+                            let escaped = chars.next().ok_or(ZoneError::new(start, ZoneErrorKind::UnterminatedString))?;
+                            line += (escaped == '\n') as usize;
+                            text.push(escaped);
+                        }
+                        Some(c) => {
+                            line += (c == '\n') as usize;
+                            text.push(c);
+                        }
+                    }
+                }
+                entry.tokens.push(Token { text, quoted: true });
+            }
+            '\\' => {
+                let token = token.get_or_insert_with(String::new);
+                token.push('\\');
+                if let Some(escaped) = chars.next() {
+                    line += (escaped == '\n') as usize;
+                    token.push(escaped);
+                }
+            }
+            c => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+    flush(&mut token, &mut entry);
+    if depth > 0 {
+        return Err(ZoneError::new(entry.line, ZoneErrorKind::UnbalancedParentheses));
+    }
+    if !entry.tokens.is_empty() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// The state carried from one entry of a file to the next.
+struct Parser {
+    origin: Name,
+    /// The TTL set by `$TTL`.
+    default_ttl: Option<u32>,
+    last_owner: Option<Name>,
+    last_ttl: Option<u32>,
+    last_class: Class,
+    /// The directory included files are relative to.
+    directory: PathBuf,
+    /// The number of `$INCLUDE` directives that led to this file.
+    depth: usize,
+    records: Vec<DNSRecord>,
+}
+
+impl Parser {
+    fn new(origin: Name, directory: PathBuf, depth: usize) -> Self {
+        Self {
+            origin,
+            default_ttl: None,
+            last_owner: None,
+            last_ttl: None,
+            last_class: Class::IN,
+            directory,
+            depth,
+            records: Vec::new(),
+        }
+    }
+
+    fn parse(&mut self, text: &str) -> Result<(), ZoneError> {
+        for entry in tokenize(text, 1)? {
+            self.parse_entry(&entry).map_err(|kind| ZoneError::new(entry.line, kind))?;
+        }
+        Ok(())
+    }
+
+    fn parse_entry(&mut self, entry: &Entry) -> Result<(), ZoneErrorKind> {
+        let first = &entry.tokens[0];
+        if entry.blank_start || first.quoted || !first.text.starts_with('$') {
+            return self.parse_record(entry);
+        }
+        let mut fields = Fields::new(&entry.tokens[1..], &self.origin);
+        match first.text.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let origin = fields.name("origin")?;
+                fields.finish()?;
+                self.origin = origin;
+            }
+            "$TTL" => {
+                let ttl = fields.ttl("TTL")?;
+                fields.finish()?;
+                self.default_ttl = Some(ttl);
+            }
+            "$INCLUDE" => {
+                let path = self.directory.join(&fields.next("file name")?.text);
+                let origin = if fields.is_empty() { self.origin.clone() } else { fields.name("origin")? };
+                fields.finish()?;
+                self.include(path, origin)?;
+            }
+            "$GENERATE" => self.generate(entry)?,
+            directive => return Err(ZoneErrorKind::UnknownDirective(directive.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Parses an included file with its own origin and adds its records. Like BIND does, the
+    /// directives of the included file do not change the state of the including one.
+    fn include(&mut self, path: PathBuf, origin: Name) -> Result<(), ZoneErrorKind> {
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(ZoneErrorKind::IncludeTooDeep);
+        }
+        let text = std::fs::read_to_string(&path).map_err(ZoneErrorKind::Io)?;
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut parser = Parser::new(origin, directory, self.depth + 1);
+        parser.default_ttl = self.default_ttl;
+        parser.last_class = self.last_class;
+        parser.parse(&text).map_err(|error| ZoneErrorKind::Include { path, error: Box::new(error) })?;
+        self.records.append(&mut parser.records);
+        Ok(())
+    }
+
+    /// Expands `$GENERATE start-stop[/step] owner [ttl] [class] type rdata`, where `$` in the
+    /// fields stands for the iterator (see `substitute`).
+    fn generate(&mut self, entry: &Entry) -> Result<(), ZoneErrorKind> {
+        let range = entry.tokens.get(1).ok_or(ZoneErrorKind::MissingField("range"))?;
+        let invalid_range = || ZoneErrorKind::InvalidField { field: "range", value: range.text.clone() };
+        let (bounds, step) = range.text.split_once('/').unwrap_or((&range.text, "1"));
+        let (start, stop) = bounds.split_once('-').ok_or_else(invalid_range)?;
+        let (start, stop, step): (u32, u32, usize) = match (start.parse(), stop.parse(), step.parse()) {
+            (Ok(start), Ok(stop), Ok(step)) if start <= stop && step > 0 => (start, stop, step),
+            _ => return Err(invalid_range()),
+        };
+        let count = (stop - start) as u64 / step as u64 + 1;
+        if count > MAX_GENERATE_RECORDS {
+            return Err(ZoneErrorKind::GenerateRangeTooLarge(count));
+        }
+        let templates = &entry.tokens[2..];
+        if templates.is_empty() {
+            return Err(ZoneErrorKind::MissingField("owner"));
+        }
+        for value in (start..=stop).step_by(step) {
+            let mut line = String::new();
+            for token in templates {
+                let text = substitute(&token.text, value)?;
+                if token.quoted {
+                    line.push_str(&format!("\"{}\" ", text));
+                } else {
+                    line.push_str(&text);
+                    line.push(' ');
+                }
+            }
+            let generated = tokenize(&line, entry.line).map_err(|error| error.kind)?;
+            for entry in generated {
+                self.parse_record(&entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `[owner] [ttl] [class] type rdata`, where the TTL and the class may come in either order.
+    fn parse_record(&mut self, entry: &Entry) -> Result<(), ZoneErrorKind> {
+        let mut tokens = entry.tokens.as_slice();
+        let name = if entry.blank_start {
+            self.last_owner.clone().ok_or(ZoneErrorKind::MissingOwner)?
+        } else {
+            let (owner, rest) = tokens.split_first().ok_or(ZoneErrorKind::MissingOwner)?;
+            tokens = rest;
+            parse_name(&owner.text, &self.origin)?
+        };
+        let mut ttl = None;
+        let mut class = None;
+        while let Some((token, rest)) = tokens.split_first().filter(|(token, _)| !token.quoted) {
+            if let Some(value) = ttl.is_none().then(|| parse_ttl(&token.text)).flatten() {
+                ttl = Some(value);
+            } else if let Some(value) = class.is_none().then(|| token.text.parse().ok()).flatten() {
+                // ANY and NONE only make sense in questions and dynamic updates, not in zone data.
+                if matches!(value, Class::ANY | Class::NONE) {
+                    return Err(ZoneErrorKind::InvalidField { field: "class", value: token.text.clone() });
+                }
+                class = Some(value);
+            } else {
+                break;
+            }
+            tokens = rest;
+        }
+        let (kind, rdata) = tokens.split_first().ok_or(ZoneErrorKind::UnknownType(String::new()))?;
+        let kind = kind.text.parse().map_err(|_| ZoneErrorKind::UnknownType(kind.text.clone()))?;
+        let ttl = ttl.or(self.default_ttl).or(self.last_ttl).ok_or(ZoneErrorKind::MissingTtl)?;
+        let class = class.unwrap_or(self.last_class);
+        let data = parse_data(kind, rdata, &self.origin)?;
+        self.last_owner = Some(name.clone());
+        self.last_ttl = Some(ttl);
+        self.last_class = class;
+        self.records.push(DNSRecord { name, kind, class, ttl, data });
+        Ok(())
+    }
+}
+
+/// Replaces the iterator of `$GENERATE` in `template`: `$` is the value, `${offset,width,base}`
+/// the value plus `offset` written with at least `width` digits in base `d`, `o`, `x` or `X`, and
+/// `\$` or `$$` a literal dollar sign.
+fn substitute(template: &str, value: u32) -> Result<String, ZoneErrorKind> {
+    let invalid = || ZoneErrorKind::InvalidField { field: "$GENERATE template", value: template.to_string() };
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => text.push(chars.next().ok_or_else(invalid)?),
+            '\\' => {
+                text.push(c);
+                text.push(chars.next().ok_or_else(invalid)?);
+            }
+            '$' if chars.peek() == Some(&'$') => text.push(chars.next().ok_or_else(invalid)?),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let modifier: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let mut parts = modifier.split(',');
+                let offset: i64 = parts.next().filter(|part| !part.is_empty()).map_or(Ok(0), str::parse).map_err(|_| invalid())?;
+                let width: usize = parts.next().map_or(Ok(0), str::parse).map_err(|_| invalid())?;
+                if width > MAX_GENERATE_WIDTH {
+                    return Err(ZoneErrorKind::GenerateWidthTooLarge(width));
+                }
+                let value = u32::try_from(value as i64 + offset).map_err(|_| invalid())?;
+                let digits = match parts.next().unwrap_or("d") {
+                    "d" => format!("{:0width$}", value),
+                    "o" => format!("{:0width$o}", value),
+                    "x" => format!("{:0width$x}", value),
+                    "X" => format!("{:0width$X}", value),
+                    _ => return Err(invalid()),
+                };
+                if parts.next().is_some() {
+                    return Err(invalid());
+                }
+                text.push_str(&digits);
+            }
+            '$' => text.push_str(&value.to_string()),
+            c => text.push(c),
+        }
+    }
+    Ok(text)
+}
+
+/// Parses a name as written in a zone file: `@` is the origin and names without a trailing dot
+/// are relative to it.
+fn parse_name(text: &str, origin: &Name) -> Result<Name, ZoneErrorKind> {
+    if text == "@" {
+        return Ok(origin.clone());
+    }
+    let invalid = |error| ZoneErrorKind::InvalidName { name: text.to_string(), error };
+    let name = Name::from_str(text).map_err(invalid)?;
+    // A trailing dot makes the name absolute unless the backslash before it escapes it.
+    let trailing_backslashes = text.strip_suffix('.').map(|text| text.len() - text.trim_end_matches('\\').len());
+    if trailing_backslashes.is_some_and(|count| count % 2 == 0) {
+        Ok(name)
+    } else {
+        name.append(origin).map_err(invalid)
+    }
+}
+
+/// Parses a TTL, either in seconds or with BIND's units such as `1h30m` or `2W`.
+fn parse_ttl(text: &str) -> Option<u32> {
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Ok(seconds) = text.parse() {
+        return Some(seconds);
+    }
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return None,
+        };
+        let value: u32 = std::mem::take(&mut number).parse().ok()?;
+        total = total.checked_add(value.checked_mul(unit)?)?;
+    }
+    number.is_empty().then_some(total)
+}
+
+/// A cursor over the fields of a directive or of the data of a record.
+struct Fields<'a> {
+    tokens: &'a [Token],
+    origin: &'a Name,
+}
+
+impl<'a> Fields<'a> {
+    fn new(tokens: &'a [Token], origin: &'a Name) -> Self {
+        Self { tokens, origin }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn next(&mut self, field: &'static str) -> Result<&'a Token, ZoneErrorKind> {
+        let (token, rest) = self.tokens.split_first().ok_or(ZoneErrorKind::MissingField(field))?;
+        self.tokens = rest;
+        Ok(token)
+    }
+
+    /// Parses the next field with `parse`, failing with an `InvalidField` error if it returns `None`.
+    fn parse<T>(&mut self, field: &'static str, parse: impl FnOnce(&str) -> Option<T>) -> Result<T, ZoneErrorKind> {
+        let token = self.next(field)?;
+        parse(&token.text).ok_or_else(|| ZoneErrorKind::InvalidField { field, value: token.text.clone() })
+    }
+
+    fn number<T: FromStr>(&mut self, field: &'static str) -> Result<T, ZoneErrorKind> {
+        self.parse(field, |text| text.parse().ok())
+    }
+
+    fn ttl(&mut self, field: &'static str) -> Result<u32, ZoneErrorKind> {
+        self.parse(field, parse_ttl)
+    }
+
+    fn name(&mut self, field: &'static str) -> Result<Name, ZoneErrorKind> {
+        parse_name(&self.next(field)?.text, self.origin)
+    }
+
+    /// Parses a `<character-string>`, quoted or not.
+    fn string(&mut self, field: &'static str) -> Result<Vec<u8>, ZoneErrorKind> {
+        self.parse(field, |text| parse_character_string(text).filter(|string| string.len() <= 255))
+    }
+
+    /// Joins the remaining fields, for data like base64 and hexadecimal that may be split in chunks.
+    fn rest(&mut self, field: &'static str) -> Result<String, ZoneErrorKind> {
+        if self.tokens.is_empty() {
+            return Err(ZoneErrorKind::MissingField(field));
+        }
+        Ok(std::mem::take(&mut self.tokens).iter().map(|token| token.text.as_str()).collect())
+    }
+
+    fn rest_decoded(&mut self, field: &'static str, decode: impl FnOnce(&str) -> Option<Vec<u8>>) -> Result<Vec<u8>, ZoneErrorKind> {
+        let text = self.rest(field)?;
+        decode(&text).ok_or(ZoneErrorKind::InvalidField { field, value: text })
+    }
+
+    /// Parses the remaining fields as record types, for the type bitmaps of NSEC and NSEC3.
+    fn rest_types(&mut self) -> Result<Vec<Kind>, ZoneErrorKind> {
+        std::mem::take(&mut self.tokens)
+            .iter()
+            .map(|token| token.text.parse().map_err(|_| ZoneErrorKind::UnknownType(token.text.clone())))
+            .collect()
+    }
+
+    /// Parses an NSEC3 salt, `-` standing for an empty salt.
+    fn salt(&mut self) -> Result<Vec<u8>, ZoneErrorKind> {
+        self.parse("salt", |text| if text == "-" { Some(Vec::new()) } else { decode_hex(text).filter(|salt| salt.len() <= 255) })
+    }
+
+    fn finish(&self) -> Result<(), ZoneErrorKind> {
+        match self.tokens.first() {
+            Some(token) => Err(ZoneErrorKind::TrailingField(token.text.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses the data of a record of type `kind` from its presentation format.
+fn parse_data(kind: Kind, tokens: &[Token], origin: &Name) -> Result<Data, ZoneErrorKind> {
+    let mut fields = Fields::new(tokens, origin);
+    if tokens.first().is_some_and(|token| !token.quoted && token.text == "\\#") {
+        fields.next("\\#")?;
+        return parse_generic_data(kind, &mut fields);
+    }
+    let data = match kind {
+        Kind::NS | Kind::CNAME | Kind::PTR | Kind::DNAME
+        | Kind::MB | Kind::MD | Kind::MF | Kind::MG | Kind::MR => Data::Host(fields.name("host name")?),
+        Kind::A => Data::IPv4(fields.parse("IPv4 address", |text| text.parse::<Ipv4Addr>().ok())?),
+        Kind::AAAA => Data::IPv6(fields.parse("IPv6 address", |text| text.parse::<Ipv6Addr>().ok())?),
+        Kind::MX | Kind::KX => Data::Mx {
+            preference: fields.number("preference")?,
+            exchange: fields.name("exchange")?,
+        },
+        Kind::SOA => Data::Soa {
+            mname: fields.name("primary name server")?,
+            rname: fields.name("responsible mailbox")?,
+            serial: fields.number("serial")?,
+            refresh: fields.ttl("refresh")?,
+            retry: fields.ttl("retry")?,
+            expire: fields.ttl("expire")?,
+            minimum: fields.ttl("minimum")?,
+        },
+        Kind::MINFO => Data::Minfo {
+            rmailbx: fields.name("responsible mailbox")?,
+            emailbx: fields.name("error mailbox")?,
+        },
+        Kind::TXT => {
+            let mut strings = vec![fields.string("text")?];
+            while !fields.is_empty() {
+                strings.push(fields.string("text")?);
+            }
+            Data::Txt(strings)
+        }
+        Kind::HINFO => Data::Hinfo {
+            cpu: fields.string("CPU")?,
+            os: fields.string("OS")?,
+        },
+        Kind::SRV => Data::Srv {
+            priority: fields.number("priority")?,
+            weight: fields.number("weight")?,
+            port: fields.number("port")?,
+            target: fields.name("target")?,
+        },
+        Kind::NAPTR => Data::Naptr {
+            order: fields.number("order")?,
+            preference: fields.number("preference")?,
+            flags: fields.string("flags")?,
+            services: fields.string("services")?,
+            regexp: fields.string("regexp")?,
+            replacement: fields.name("replacement")?,
+        },
+        Kind::SSHFP => Data::Sshfp {
+            algorithm: fields.number("algorithm")?,
+            fingerprint_type: fields.number("fingerprint type")?,
+            fingerprint: fields.rest_decoded("fingerprint", decode_hex)?,
+        },
+        Kind::TLSA | Kind::SMIMEA => Data::Tlsa {
+            usage: fields.number("certificate usage")?,
+            selector: fields.number("selector")?,
+            matching_type: fields.number("matching type")?,
+            data: fields.rest_decoded("certificate association data", decode_hex)?,
+        },
+        Kind::CAA => Data::Caa {
+            flags: fields.number("flags")?,
+            tag: fields.string("tag")?,
+            value: fields.parse("value", parse_character_string)?,
+        },
+        Kind::URI => Data::Uri {
+            priority: fields.number("priority")?,
+            weight: fields.number("weight")?,
+            target: fields.parse("target", parse_character_string)?,
+        },
+        Kind::DNSKEY | Kind::CDNSKEY | Kind::KEY => Data::Dnskey {
+            flags: fields.number("flags")?,
+            protocol: fields.number("protocol")?,
+            algorithm: fields.number("algorithm")?,
+            public_key: fields.rest_decoded("public key", decode_base64)?,
+        },
+        Kind::DS | Kind::CDS | Kind::DLV | Kind::TA => Data::Ds {
+            key_tag: fields.number("key tag")?,
+            algorithm: fields.number("algorithm")?,
+            digest_type: fields.number("digest type")?,
+            digest: fields.rest_decoded("digest", decode_hex)?,
+        },
+        Kind::RRSIG | Kind::SIG => Data::Rrsig {
+            type_covered: fields.parse("type covered", |text| text.parse().ok())?,
+            algorithm: fields.number("algorithm")?,
+            labels: fields.number("labels")?,
+            original_ttl: fields.ttl("original TTL")?,
+            expiration: fields.parse("expiration", parse_timestamp)?,
+            inception: fields.parse("inception", parse_timestamp)?,
+            key_tag: fields.number("key tag")?,
+            signer_name: fields.name("signer name")?,
+            signature: fields.rest_decoded("signature", decode_base64)?,
+        },
+        Kind::NSEC => Data::Nsec {
+            next_domain: fields.name("next domain name")?,
+            types: fields.rest_types()?,
+        },
+        Kind::NSEC3 => Data::Nsec3 {
+            hash_algorithm: fields.number("hash algorithm")?,
+            flags: fields.number("flags")?,
+            iterations: fields.number("iterations")?,
+            salt: fields.salt()?,
+            next_hashed_owner: fields.parse("next hashed owner name", decode_base32hex)?,
+            types: fields.rest_types()?,
+        },
+        Kind::NSEC3PARAM => Data::Nsec3Param {
+            hash_algorithm: fields.number("hash algorithm")?,
+            flags: fields.number("flags")?,
+            iterations: fields.number("iterations")?,
+            salt: fields.salt()?,
+        },
+        Kind::SVCB | Kind::HTTPS => Data::Svcb {
+            priority: fields.number("priority")?,
+            target: fields.name("target")?,
+            params: parse_svc_params(&mut fields)?,
+        },
+        kind => return Err(ZoneErrorKind::GenericDataRequired(kind)),
+    };
+    fields.finish()?;
+    Ok(data)
+}
+
+/// Parses the service parameters of an SVCB or HTTPS record, `key`, `key=value` or `key="value"`,
+/// and sorts them by key. The keys listed by `mandatory` must be present.
+fn parse_svc_params(fields: &mut Fields) -> Result<Vec<SvcParam>, ZoneErrorKind> {
+    let mut params: Vec<SvcParam> = Vec::new();
+    while !fields.is_empty() {
+        let token = fields.next("service parameter")?;
+        let (key, value) = match token.text.split_once('=') {
+            // The tokenizer splits `key="value"` in two fields, while a bare `key=` has an empty value.
+            Some((key, "")) if fields.tokens.first().is_some_and(|next| next.quoted) => {
+                (key, fields.next("service parameter value")?.text.as_str())
+            }
+            Some((key, value)) => (key, value),
+            None => (token.text.as_str(), ""),
+        };
+        let has_value = token.text.contains('=');
+        let invalid = || ZoneErrorKind::InvalidField { field: "service parameter", value: token.text.clone() };
+        let value = if has_value { Some(parse_character_string(value).ok_or_else(invalid)?) } else { None };
+        let param = SvcParam::parse(key, value.as_deref()).ok_or_else(invalid)?;
+        if params.iter().any(|other| other.key() == param.key()) {
+            return Err(invalid());
+        }
+        params.push(param);
+    }
+    params.sort_by_key(|param| u16::from(param.key()));
+    // Every mandatory key must be present in the record (RFC 9460 section 8).
+    if let Some(mandatory @ SvcParam::Mandatory(keys)) = params.first() {
+        if keys.iter().any(|key| params.iter().all(|param| param.key() != *key)) {
+            return Err(ZoneErrorKind::InvalidField { field: "mandatory", value: mandatory.to_string() });
+        }
+    }
+    Ok(params)
+}
+
+/// Parses the generic data format of RFC 3597 section 5, `\# length hex`, and decodes it for the
+/// record type when it is a known one.
+fn parse_generic_data(kind: Kind, fields: &mut Fields) -> Result<Data, ZoneErrorKind> {
+    let len: usize = fields.number("data length")?;
+    let bytes = if len == 0 { Vec::new() } else { fields.rest_decoded("data", decode_hex)? };
+    fields.finish()?;
+    if bytes.len() != len {
+        return Err(ZoneErrorKind::InvalidGenericData(format!("{} bytes of data for a length of {}", bytes.len(), len)));
+    }
+    Reader::new(&bytes)
+        .with_limit(len, |reader| decode_data(reader, kind))
+        .map_err(|err| ZoneErrorKind::InvalidGenericData(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> Name {
+        "example.com.".parse().unwrap()
+    }
+
+    fn parse(text: &str) -> Vec<String> {
+        match parse_zone(text, &origin()) {
+            Ok(records) => records.iter().map(DNSRecord::to_string).collect(),
+            Err(error) => panic!("the zone should parse: {error}"),
+        }
+    }
+
+    fn parse_error(text: &str) -> ZoneError {
+        parse_zone(text, &origin()).expect_err("the zone should not parse")
+    }
+
+    #[test]
+    fn relative_names_and_repeated_owners() {
+        let records = parse(concat!(
+            "@ 300 IN NS ns1\n",
+            "www 300 A 192.0.2.1\n",
+            "    AAAA 2001:db8::1 ; the owner and the TTL of the previous record\n",
+            "mail.example.net. 60 MX 10 mx\n",
+            "a\\.b 60 CNAME www.example.com.\n",
+        ));
+        assert_eq!(records, [
+            "example.com.\t300\tIN\tNS\tns1.example.com.",
+            "www.example.com.\t300\tIN\tA\t192.0.2.1",
+            "www.example.com.\t300\tIN\tAAAA\t2001:db8::1",
+            "mail.example.net.\t60\tIN\tMX\t10 mx.example.com.",
+            "a\\.b.example.com.\t60\tIN\tCNAME\twww.example.com.",
+        ]);
+    }
+
+    #[test]
+    fn origin_directive() {
+        let records = parse("$ORIGIN sub.example.com.\nhost 60 A 192.0.2.1\n$ORIGIN other\n@ 60 A 192.0.2.2\n");
+        assert_eq!(records, [
+            "host.sub.example.com.\t60\tIN\tA\t192.0.2.1",
+            "other.sub.example.com.\t60\tIN\tA\t192.0.2.2",
+        ]);
+    }
+
+    #[test]
+    fn ttl_directive_and_units() {
+        let records = parse("$TTL 1h\na A 192.0.2.1\nb 1w2d3h4m5s A 192.0.2.2\nc A 192.0.2.3\n$TTL 90\nd A 192.0.2.4\n");
+        assert_eq!(records, [
+            "a.example.com.\t3600\tIN\tA\t192.0.2.1",
+            "b.example.com.\t788645\tIN\tA\t192.0.2.2",
+            "c.example.com.\t3600\tIN\tA\t192.0.2.3",
+            "d.example.com.\t90\tIN\tA\t192.0.2.4",
+        ]);
+        assert!(matches!(parse_error("a A 192.0.2.1").kind, ZoneErrorKind::MissingTtl));
+        assert!(matches!(parse_error("$TTL 1y").kind, ZoneErrorKind::InvalidField { field: "TTL", .. }));
+    }
+
+    #[test]
+    fn parentheses_span_lines() {
+        let records = parse(concat!(
+            "@ 300 IN SOA ns1 hostmaster (\n",
+            "    2024010101 ; serial\n",
+            "    7200 3600 1209600\n",
+            "    300 )\n",
+            "txt 300 TXT \"a (quoted) ; string\"\n",
+        ));
+        assert_eq!(records, [
+            "example.com.\t300\tIN\tSOA\tns1.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 300",
+            "txt.example.com.\t300\tIN\tTXT\t\"a (quoted) ; string\"",
+        ]);
+        let error = parse_error("a 60 A 192.0.2.1\nb 60 TXT ( \"x\"\n");
+        assert!(matches!(error.kind, ZoneErrorKind::UnbalancedParentheses));
+        assert_eq!(error.line, 2);
+        assert!(matches!(parse_error("a 60 A 192.0.2.1 )").kind, ZoneErrorKind::UnbalancedParentheses));
+    }
+
+    #[test]
+    fn errors_report_the_line() {
+        let error = parse_error("a 60 A 192.0.2.1\n\n; comment\nb 60 A 192.0.2\n");
+        assert_eq!(error.line, 4);
+        assert!(matches!(error.kind, ZoneErrorKind::InvalidField { field: "IPv4 address", .. }));
+        assert!(matches!(parse_error("$FOO x").kind, ZoneErrorKind::UnknownDirective(_)));
+        assert!(matches!(parse_error(" 60 A 192.0.2.1").kind, ZoneErrorKind::MissingOwner));
+    }
+
+    #[test]
+    fn include_directive() {
+        let directory = std::env::temp_dir().join(format!("rsolver-zone-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("main.zone"), concat!(
+            "$TTL 60\n",
+            "$INCLUDE sub.zone sub.example.com.\n",
+            "after A 192.0.2.3\n",
+            "$INCLUDE broken.zone\n",
+        )).unwrap();
+        std::fs::write(directory.join("sub.zone"), "host A 192.0.2.1\n$ORIGIN other.example.com.\n@ A 192.0.2.2\n").unwrap();
+        std::fs::write(directory.join("broken.zone"), "ok A 192.0.2.4\nbad A x\n").unwrap();
+        std::fs::write(directory.join("loop.zone"), "$INCLUDE loop.zone\n").unwrap();
+
+        let error = parse_zone_file(directory.join("main.zone"), &origin()).unwrap_err();
+        assert_eq!(error.line, 4);
+        let ZoneErrorKind::Include { path, error } = error.kind else {
+            panic!("the error should come from the included file");
+        };
+        assert_eq!(path, directory.join("broken.zone"));
+        assert_eq!(error.line, 2);
+
+        std::fs::write(directory.join("main.zone"), "$TTL 60\n$INCLUDE sub.zone sub.example.com.\nafter A 192.0.2.3\n").unwrap();
+        let records: Vec<String> = parse_zone_file(directory.join("main.zone"), &origin()).unwrap()
+            .iter().map(DNSRecord::to_string).collect();
+        // The origin changed by the included file does not leak back into the including one.
+        assert_eq!(records, [
+            "host.sub.example.com.\t60\tIN\tA\t192.0.2.1",
+            "other.example.com.\t60\tIN\tA\t192.0.2.2",
+            "after.example.com.\t60\tIN\tA\t192.0.2.3",
+        ]);
+
+        let mut error = parse_zone_file(directory.join("loop.zone"), &origin()).unwrap_err();
+        while let ZoneErrorKind::Include { error: inner, .. } = error.kind {
+            error = *inner;
+        }
+        assert!(matches!(error.kind, ZoneErrorKind::IncludeTooDeep));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn generate_directive() {
+        let records = parse(concat!(
+            "$GENERATE 1-3 host$ 60 A 192.0.2.$\n",
+            "$GENERATE 0-4/2 ${10,3,x}.rev 60 PTR host-${0,2}.example.com.\n",
+            "$GENERATE 1-1 $$\\$ 60 TXT \"item $\"\n",
+        ));
+        assert_eq!(records, [
+            "host1.example.com.\t60\tIN\tA\t192.0.2.1",
+            "host2.example.com.\t60\tIN\tA\t192.0.2.2",
+            "host3.example.com.\t60\tIN\tA\t192.0.2.3",
+            "00a.rev.example.com.\t60\tIN\tPTR\thost-00.example.com.",
+            "00c.rev.example.com.\t60\tIN\tPTR\thost-02.example.com.",
+            "00e.rev.example.com.\t60\tIN\tPTR\thost-04.example.com.",
+            "\\$\\$.example.com.\t60\tIN\tTXT\t\"item 1\"",
+        ]);
+    }
+
+    #[test]
+    fn generate_limits() {
+        assert!(matches!(
+            parse_error("$GENERATE 0-4294967295 host$ 60 A 192.0.2.1").kind,
+            ZoneErrorKind::GenerateRangeTooLarge(4294967296)
+        ));
+        assert_eq!(parse_zone("$GENERATE 0-131071/2 host$ 60 A 192.0.2.1", &origin()).unwrap().len(), 65536);
+        assert!(matches!(
+            parse_error("$GENERATE 1-2 ${0,999999999} 60 A 192.0.2.1").kind,
+            ZoneErrorKind::GenerateWidthTooLarge(999999999)
+        ));
+        assert!(matches!(parse_error("$GENERATE 3-1 host$ 60 A 192.0.2.1").kind, ZoneErrorKind::InvalidField { field: "range", .. }));
+        assert!(matches!(parse_error("$GENERATE 1-2 ${0,2,z} 60 A 192.0.2.1").kind, ZoneErrorKind::InvalidField { .. }));
+    }
+
+    #[test]
+    fn rejects_meta_classes() {
+        for class in ["ANY", "none", "CLASS255", "CLASS254"] {
+            let error = parse_error(&format!("a 60 {class} A 192.0.2.1"));
+            assert!(matches!(error.kind, ZoneErrorKind::InvalidField { field: "class", value } if value == class));
+        }
+        assert_eq!(parse("a 60 CH TXT x\nb 60 CLASS3 TXT y"), [
+            "a.example.com.\t60\tCH\tTXT\t\"x\"",
+            "b.example.com.\t60\tCH\tTXT\t\"y\"",
+        ]);
+    }
+
+    #[test]
+    fn service_parameters() {
+        // RFC 9460 appendix D, figures 2 to 9, in presentation format.
+        let records = parse(concat!(
+            "a 60 SVCB 0 foo.example.com.\n",
+            "b 60 SVCB 1 .\n",
+            "c 60 SVCB 16 foo.example.com. port=53\n",
+            "d 60 SVCB 1 foo.example.com. key667=hello\n",
+            "e 60 SVCB 1 foo.example.com. key667=\"hello\\210qoo\"\n",
+            "f 60 SVCB 1 foo.example.com. ipv6hint=\"2001:db8::1,2001:db8::53:1\"\n",
+            "g 60 SVCB 1 example.com. ipv6hint=\"2001:db8:122:344::192.0.2.33\"\n",
+            "h 60 SVCB 16 foo.example.org. alpn=h2,h3-19 mandatory=ipv4hint,alpn ipv4hint=192.0.2.1\n",
+        ));
+        assert_eq!(records, [
+            "a.example.com.\t60\tIN\tSVCB\t0 foo.example.com.",
+            "b.example.com.\t60\tIN\tSVCB\t1 .",
+            "c.example.com.\t60\tIN\tSVCB\t16 foo.example.com. port=53",
+            "d.example.com.\t60\tIN\tSVCB\t1 foo.example.com. key667=\"hello\"",
+            "e.example.com.\t60\tIN\tSVCB\t1 foo.example.com. key667=\"hello\\210qoo\"",
+            "f.example.com.\t60\tIN\tSVCB\t1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1",
+            "g.example.com.\t60\tIN\tSVCB\t1 example.com. ipv6hint=2001:db8:122:344::c000:221",
+            "h.example.com.\t60\tIN\tSVCB\t16 foo.example.org. mandatory=alpn,ipv4hint alpn=\"h2,h3-19\" ipv4hint=192.0.2.1",
+        ]);
+    }
+
+    #[test]
+    fn service_parameter_values() {
+        // A bare `key=` has an empty value and does not take the next field as its value.
+        assert_eq!(parse("a 60 SVCB 1 . key65000= port=443"), ["a.example.com.\t60\tIN\tSVCB\t1 . port=443 key65000"]);
+        assert_eq!(parse("a 60 SVCB 1 . key65000=\"\""), ["a.example.com.\t60\tIN\tSVCB\t1 . key65000"]);
+        assert_eq!(parse("a 60 SVCB 1 . alpn=\"h2,h3\""), ["a.example.com.\t60\tIN\tSVCB\t1 . alpn=\"h2,h3\""]);
+        for params in [
+            "alpn= h2",
+            "port=",
+            "port=443 port=53",
+            "no-default-alpn=",
+            "mandatory=alpn,alpn alpn=h2",
+            "mandatory=mandatory,alpn alpn=h2",
+            "mandatory=port",
+            "bogus=1",
+        ] {
+            let error = parse_error(&format!("a 60 SVCB 1 . {params}"));
+            assert!(matches!(error.kind, ZoneErrorKind::InvalidField { .. } | ZoneErrorKind::TrailingField(_)), "{params}");
+        }
+    }
+}
@@ -1,15 +1,103 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use clap::ValueEnum;
-use derive_try_from_primitive::TryFromPrimitive;
 use strum::EnumIter;
 
+/// Declares an enum of 16-bit DNS parameters together with its conversions from and to `u16`.
+///
+/// The first variant must be a tuple variant holding a `u16`; it keeps the values that have no
+/// variant of their own, so decoding never fails and re-encoding is lossless.
+macro_rules! dns_parameter_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(#[$unknown_meta:meta])*
+            $unknown:ident(u16),
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:literal,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+            $(#[$unknown_meta])*
+            $unknown(u16),
+        }
+
+        impl From<u16> for $name {
+            fn from(value: u16) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    value => $name::$unknown(value),
+                }
+            }
+        }
+
+        impl $name {
+            /// Returns the variant named `mnemonic`, ignoring ASCII case.
+            #[allow(dead_code)]
+            fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+                $(
+                    if mnemonic.eq_ignore_ascii_case(stringify!($variant)) {
+                        return Some($name::$variant);
+                    }
+                )*
+                None
+            }
+        }
+
+        impl From<$name> for u16 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::$unknown(value) => value,
+                }
+            }
+        }
+    };
+}
+
+/// An error returned when a string is neither the mnemonic nor the generic form of a DNS parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterParseError {
+    /// The parameter that was expected, like `type` or `class`.
+    pub parameter: &'static str,
+    /// The string that was parsed.
+    pub value: String,
+}
+
+impl Display for ParameterParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown {} {:?}", self.parameter, self.value)
+    }
+}
+
+impl std::error::Error for ParameterParseError {}
+
+/// Parses the generic form of RFC 3597 section 5, `prefix` followed by a decimal value, ignoring ASCII case.
+fn parse_generic(text: &str, prefix: &str) -> Option<u16> {
+    let value = text.get(prefix.len()..).filter(|_| text[..prefix.len()].eq_ignore_ascii_case(prefix))?;
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+dns_parameter_enum! {
 /// This is a Rust implementation of the DNS record types defined in RFC 1035 section 3.2.2.
 /// Extended using https://en.wikipedia.org/wiki/List_of_DNS_record_types
 /// The `Kind` enum represents the different types of DNS records, with each variant corresponding to a specific record type.
 /// The values assigned to each variant represent the numerical value of the record type as defined in the RFC.
-#[repr(u16)]
-#[derive(Copy, Clone, Default, Debug, ValueEnum, Eq, PartialEq)]
-#[derive(TryFromPrimitive)]
+#[derive(Copy, Clone, Default, Debug, ValueEnum, Eq, PartialEq, Hash)]
 pub enum Kind {
+    /// A record type not known by this implementation, presented as `TYPE` followed by its value (RFC 3597).
+    #[value(skip)]
+    Unknown(u16),
     #[default]
     /// An A record type, used for storing an IP address.
     A = 1,
@@ -65,6 +153,9 @@ pub enum Kind {
     CERT = 37,
     /// Delegation name record - RFC 6672
     DNAME = 39,
+    /// EDNS option pseudo-record - RFC 6891 (Meta type, never queried)
+    #[value(skip)]
+    OPT = 41,
     /// Address Prefix List - RFC 3123
     APL = 42,
     /// Delegation signer - RFC 4034
@@ -113,6 +204,16 @@ pub enum Kind {
     TKEY = 249,
     /// Transaction Signature - RFC 2845
     TSIG = 250,
+    /// Incremental zone transfer - RFC 1995 (QTYPE only)
+    IXFR = 251,
+    /// Transfer of an entire zone - RFC 1035 (QTYPE only)
+    AXFR = 252,
+    /// Mailbox-related records, MB, MG or MR - RFC 1035 (QTYPE only)
+    MAILB = 253,
+    /// Mail agent records, MD or MF - RFC 1035 (QTYPE only, Obsolete - see MX)
+    MAILA = 254,
+    /// A request for all the records available at the name - RFC 1035 (QTYPE only)
+    ANY = 255,
     /// Uniform Resource Identifier - RFC 7553
     URI = 256,
     /// Certification Authority Authorization - RFC 6844
@@ -121,17 +222,64 @@ pub enum Kind {
     TA = 32768,
     /// DNSSEC Lookaside Validation record - RFC 4431
     DLV = 32769,
-    /// Don't filter any response
-    ANY,
+}
+}
+
+impl Kind {
+    /// Returns whether this type only makes sense as a QTYPE (IXFR, AXFR, MAILB, MAILA and ANY)
+    /// and never appears as the type of a record.
+    pub fn is_qtype_only(&self) -> bool {
+        matches!(self, Kind::IXFR | Kind::AXFR | Kind::MAILB | Kind::MAILA | Kind::ANY)
+    }
+
+    /// Returns whether this is a meta type (RFC 6895 section 3.1): a type that only carries
+    /// transient data about a message, like OPT, TKEY and TSIG, or a QTYPE only type.
+    pub fn is_meta(&self) -> bool {
+        matches!(self, Kind::OPT | Kind::TKEY | Kind::TSIG) || self.is_qtype_only()
+    }
+
+    /// Returns whether a record of type `kind` answers a question with this QTYPE.
+    ///
+    /// ANY matches every type, MAILB and MAILA match the mailbox and mail agent records, and any
+    /// other type only matches itself.
+    pub fn matches(&self, kind: Kind) -> bool {
+        match self {
+            Kind::ANY => !kind.is_meta(),
+            Kind::MAILB => matches!(kind, Kind::MB | Kind::MG | Kind::MR),
+            Kind::MAILA => matches!(kind, Kind::MD | Kind::MF),
+            qtype => *qtype == kind,
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Unknown(value) => write!(f, "TYPE{}", value),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = ParameterParseError;
+
+    /// Parses a type mnemonic like `MX`, ignoring case, or the generic form like `TYPE65534`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::from_mnemonic(text)
+            .or_else(|| parse_generic(text, "TYPE").map(Kind::from))
+            .ok_or_else(|| ParameterParseError { parameter: "type", value: text.to_string() })
+    }
 }
 
+dns_parameter_enum! {
 /// This is a Rust implementation of the DNS class types defined in RFC 1035 section 3.2.4.
 /// The `Class` enum represents the different classes of DNS records, with each variant corresponding to a specific class type.
 /// The values assigned to each variant represent the numerical value of the class type as defined in the RFC.
-#[repr(u16)]
-#[derive(TryFromPrimitive)]
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Hash)]
 pub enum Class {
+    /// A class not known by this implementation, presented as `CLASS` followed by its value (RFC 3597).
+    Unknown(u16),
     #[default]
     /// The Internet class.
     IN = 1,
@@ -141,6 +289,38 @@ pub enum Class {
     CH = 3,
     /// Hesiod [Dyer 87].
     HS = 4,
+    /// No class, used by dynamic updates to delete RRsets - RFC 2136 (QCLASS only)
+    NONE = 254,
+    /// Any class - RFC 1035 (QCLASS only)
+    ANY = 255,
+}
+}
+
+impl Class {
+    /// Returns whether a record of class `class` answers a question with this QCLASS.
+    pub fn matches(&self, class: Class) -> bool {
+        *self == Class::ANY || *self == class
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Class::Unknown(value) => write!(f, "CLASS{}", value),
+            class => write!(f, "{:?}", class),
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = ParameterParseError;
+
+    /// Parses a class mnemonic like `IN`, ignoring case, or the generic form like `CLASS3`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::from_mnemonic(text)
+            .or_else(|| parse_generic(text, "CLASS").map(Class::from))
+            .ok_or_else(|| ParameterParseError { parameter: "class", value: text.to_string() })
+    }
 }
 
 /// DNS Header Flags
@@ -155,30 +335,417 @@ pub enum Flag {
     /// Authoritative Answer (AA)
     ///
     /// This 1-bit flag is set to 1 in a response if the responding server is an authority for the domain name used in the question.
-    AA = 1 << 5,
+    AA = 1 << 10,
 
     /// Truncated Response (TC)
     ///
     /// This 1-bit flag is set to 1 if the message was truncated due to its length exceeding the maximum allowed by the underlying transport protocol.
-    TC = 1 << 6,
+    TC = 1 << 9,
 
     /// Recursion Desired (RD)
     ///
     /// This 1-bit flag is set to 1 in a query if the client wants the server to perform recursion to resolve the query.
-    RD = 1 << 7,
+    RD = 1 << 8,
 
     /// Recursion Available (RA)
     ///
     /// This 1-bit flag is set to 1 in a response if the server supports recursion.
-    RA = 1 << 8,
+    RA = 1 << 7,
 
     /// Authentic Data (AD)
     ///
     /// This 1-bit flag is set to 1 in a response if all the data included in the answer and authority sections of the response have been authenticated by the server according to the policies of that server.
-    AD = 1 << 10,
+    AD = 1 << 5,
 
     /// Checking Disabled (CD)
     ///
     /// This 1-bit flag is set to 1 in a query to disable checking of data authenticity by the server.
-    CD = 1 << 11,
-}
\ No newline at end of file
+    CD = 1 << 4,
+}
+
+/// DNS Operation Code
+///
+/// A four bit field in the DNS header that specifies the kind of query in this message.
+///
+/// For more information, see [IANA's website](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5).
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+pub enum Opcode {
+    #[default]
+    /// A standard query - RFC 1035
+    QUERY,
+    /// An inverse query (Obsolete) - RFC 3425
+    IQUERY,
+    /// A server status request - RFC 1035
+    STATUS,
+    /// A zone change notification - RFC 1996
+    NOTIFY,
+    /// A dynamic update - RFC 2136
+    UPDATE,
+    /// DNS Stateful Operations - RFC 8490
+    DSO,
+    /// An opcode without an assigned meaning.
+    Unassigned(u8),
+}
+
+impl From<u8> for Opcode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Opcode::QUERY,
+            1 => Opcode::IQUERY,
+            2 => Opcode::STATUS,
+            4 => Opcode::NOTIFY,
+            5 => Opcode::UPDATE,
+            6 => Opcode::DSO,
+            value => Opcode::Unassigned(value),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(value: Opcode) -> Self {
+        match value {
+            Opcode::QUERY => 0,
+            Opcode::IQUERY => 1,
+            Opcode::STATUS => 2,
+            Opcode::NOTIFY => 4,
+            Opcode::UPDATE => 5,
+            Opcode::DSO => 6,
+            Opcode::Unassigned(value) => value,
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Opcode::Unassigned(value) => write!(f, "OPCODE{}", value),
+            opcode => write!(f, "{:?}", opcode),
+        }
+    }
+}
+
+/// DNS Response Code
+///
+/// The header carries the lower four bits of the response code; once EDNS is in use the OPT record
+/// carries the upper eight bits, which gives room for the extended response codes (16 and above).
+///
+/// For more information, see [IANA's website](https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6).
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+pub enum Rcode {
+    #[default]
+    /// No Error - RFC 1035
+    NOERROR,
+    /// Format Error - RFC 1035
+    FORMERR,
+    /// Server Failure - RFC 1035
+    SERVFAIL,
+    /// Non-Existent Domain - RFC 1035
+    NXDOMAIN,
+    /// Not Implemented - RFC 1035
+    NOTIMP,
+    /// Query Refused - RFC 1035
+    REFUSED,
+    /// Name Exists when it should not - RFC 2136
+    YXDOMAIN,
+    /// RR Set Exists when it should not - RFC 2136
+    YXRRSET,
+    /// RR Set that should exist does not - RFC 2136
+    NXRRSET,
+    /// Server Not Authoritative for zone - RFC 2136
+    NOTAUTH,
+    /// Name not contained in zone - RFC 2136
+    NOTZONE,
+    /// DSO-TYPE Not Implemented - RFC 8490
+    DSOTYPENI,
+    /// Bad OPT Version - RFC 6891
+    BADVERS,
+    /// Key not recognized - RFC 8945
+    BADKEY,
+    /// Signature out of time window - RFC 8945
+    BADTIME,
+    /// Bad TKEY Mode - RFC 2930
+    BADMODE,
+    /// Duplicate key name - RFC 2930
+    BADNAME,
+    /// Algorithm not supported - RFC 2930
+    BADALG,
+    /// Bad Truncation - RFC 8945
+    BADTRUNC,
+    /// Bad/missing Server Cookie - RFC 7873
+    BADCOOKIE,
+    /// A response code without an assigned meaning.
+    Unassigned(u16),
+}
+
+impl Rcode {
+    /// Composes a response code from the four bits of the header and the upper eight bits carried by the OPT record.
+    pub fn extended(header: u8, upper: u8) -> Self {
+        Rcode::from((upper as u16) << 4 | (header & 0x0f) as u16)
+    }
+
+    /// Returns the lower four bits of the response code, the part that is carried in the DNS header.
+    pub fn header_bits(self) -> u8 {
+        (u16::from(self) & 0x0f) as u8
+    }
+
+    /// Returns the upper eight bits of the response code, the part that is carried in the OPT record.
+    pub fn extended_bits(self) -> u8 {
+        (u16::from(self) >> 4) as u8
+    }
+}
+
+impl From<u16> for Rcode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Rcode::NOERROR,
+            1 => Rcode::FORMERR,
+            2 => Rcode::SERVFAIL,
+            3 => Rcode::NXDOMAIN,
+            4 => Rcode::NOTIMP,
+            5 => Rcode::REFUSED,
+            6 => Rcode::YXDOMAIN,
+            7 => Rcode::YXRRSET,
+            8 => Rcode::NXRRSET,
+            9 => Rcode::NOTAUTH,
+            10 => Rcode::NOTZONE,
+            11 => Rcode::DSOTYPENI,
+            16 => Rcode::BADVERS,
+            17 => Rcode::BADKEY,
+            18 => Rcode::BADTIME,
+            19 => Rcode::BADMODE,
+            20 => Rcode::BADNAME,
+            21 => Rcode::BADALG,
+            22 => Rcode::BADTRUNC,
+            23 => Rcode::BADCOOKIE,
+            value => Rcode::Unassigned(value),
+        }
+    }
+}
+
+impl From<Rcode> for u16 {
+    fn from(value: Rcode) -> Self {
+        match value {
+            Rcode::NOERROR => 0,
+            Rcode::FORMERR => 1,
+            Rcode::SERVFAIL => 2,
+            Rcode::NXDOMAIN => 3,
+            Rcode::NOTIMP => 4,
+            Rcode::REFUSED => 5,
+            Rcode::YXDOMAIN => 6,
+            Rcode::YXRRSET => 7,
+            Rcode::NXRRSET => 8,
+            Rcode::NOTAUTH => 9,
+            Rcode::NOTZONE => 10,
+            Rcode::DSOTYPENI => 11,
+            Rcode::BADVERS => 16,
+            Rcode::BADKEY => 17,
+            Rcode::BADTIME => 18,
+            Rcode::BADMODE => 19,
+            Rcode::BADNAME => 20,
+            Rcode::BADALG => 21,
+            Rcode::BADTRUNC => 22,
+            Rcode::BADCOOKIE => 23,
+            Rcode::Unassigned(value) => value,
+        }
+    }
+}
+
+impl Display for Rcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rcode::Unassigned(value) => write!(f, "RESERVED{}", value),
+            rcode => write!(f, "{:?}", rcode),
+        }
+    }
+}
+
+dns_parameter_enum! {
+/// The INFO-CODE of an Extended DNS Error option, which tells why a response failed or what it is
+/// based on, as registered in the "Extended DNS Error Codes" IANA registry.
+///
+/// For more information, see [RFC 8914 section 4](https://www.rfc-editor.org/rfc/rfc8914#section-4).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ExtendedErrorCode {
+    /// A code not known by this implementation.
+    Unknown(u16),
+    /// None of the other codes apply - RFC 8914
+    OtherError = 0,
+    /// The DNSKEY RRset uses only unsupported algorithms - RFC 8914
+    UnsupportedDnskeyAlgorithm = 1,
+    /// The DS RRset uses only unsupported digest types - RFC 8914
+    UnsupportedDsDigestType = 2,
+    /// The answer comes from stale cached data - RFC 8914
+    StaleAnswer = 3,
+    /// The answer was forged by a policy - RFC 8914
+    ForgedAnswer = 4,
+    /// DNSSEC validation ended in the Indeterminate state - RFC 8914
+    DnssecIndeterminate = 5,
+    /// DNSSEC validation ended in the Bogus state - RFC 8914
+    DnssecBogus = 6,
+    /// No signature is still valid - RFC 8914
+    SignatureExpired = 7,
+    /// No signature is valid yet - RFC 8914
+    SignatureNotYetValid = 8,
+    /// No DNSKEY matches the DS records of the zone - RFC 8914
+    DnskeyMissing = 9,
+    /// No RRSIGs were found to validate the answer - RFC 8914
+    RrsigsMissing = 10,
+    /// No DNSKEY has the Zone Key bit set - RFC 8914
+    NoZoneKeyBitSet = 11,
+    /// The denial of existence cannot be proven - RFC 8914
+    NsecMissing = 12,
+    /// The server answered with a cached error - RFC 8914
+    CachedError = 13,
+    /// The server is not ready to serve the query - RFC 8914
+    NotReady = 14,
+    /// The domain is on a blocklist of the operator - RFC 8914
+    Blocked = 15,
+    /// The domain is blocked because an external party requires it - RFC 8914
+    Censored = 16,
+    /// The domain is blocked because the client asked for it - RFC 8914
+    Filtered = 17,
+    /// The client is not allowed to query the server - RFC 8914
+    Prohibited = 18,
+    /// The NXDOMAIN answer comes from stale cached data - RFC 8914
+    StaleNxdomainAnswer = 19,
+    /// The server is not authoritative for the zone and does not recurse - RFC 8914
+    NotAuthoritative = 20,
+    /// The operation or query is not supported - RFC 8914
+    NotSupported = 21,
+    /// No authoritative name server could be reached - RFC 8914
+    NoReachableAuthority = 22,
+    /// An unrecoverable network error happened - RFC 8914
+    NetworkError = 23,
+    /// The authoritative data of the zone is invalid - RFC 8914
+    InvalidData = 24,
+    /// The signature expired before it became valid - RFC 9077
+    SignatureExpiredBeforeValid = 25,
+    /// The query was sent as 0-RTT data too early - RFC 9250
+    TooEarly = 26,
+    /// The NSEC3 records use an unsupported number of iterations - RFC 9276
+    UnsupportedNsec3IterationsValue = 27,
+}
+}
+
+impl Display for ExtendedErrorCode {
+    /// Writes the code followed by its name in the IANA registry, the way dig does.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ExtendedErrorCode::Unknown(_) => "Unknown",
+            ExtendedErrorCode::OtherError => "Other Error",
+            ExtendedErrorCode::UnsupportedDnskeyAlgorithm => "Unsupported DNSKEY Algorithm",
+            ExtendedErrorCode::UnsupportedDsDigestType => "Unsupported DS Digest Type",
+            ExtendedErrorCode::StaleAnswer => "Stale Answer",
+            ExtendedErrorCode::ForgedAnswer => "Forged Answer",
+            ExtendedErrorCode::DnssecIndeterminate => "DNSSEC Indeterminate",
+            ExtendedErrorCode::DnssecBogus => "DNSSEC Bogus",
+            ExtendedErrorCode::SignatureExpired => "Signature Expired",
+            ExtendedErrorCode::SignatureNotYetValid => "Signature Not Yet Valid",
+            ExtendedErrorCode::DnskeyMissing => "DNSKEY Missing",
+            ExtendedErrorCode::RrsigsMissing => "RRSIGs Missing",
+            ExtendedErrorCode::NoZoneKeyBitSet => "No Zone Key Bit Set",
+            ExtendedErrorCode::NsecMissing => "NSEC Missing",
+            ExtendedErrorCode::CachedError => "Cached Error",
+            ExtendedErrorCode::NotReady => "Not Ready",
+            ExtendedErrorCode::Blocked => "Blocked",
+            ExtendedErrorCode::Censored => "Censored",
+            ExtendedErrorCode::Filtered => "Filtered",
+            ExtendedErrorCode::Prohibited => "Prohibited",
+            ExtendedErrorCode::StaleNxdomainAnswer => "Stale NXDOMAIN Answer",
+            ExtendedErrorCode::NotAuthoritative => "Not Authoritative",
+            ExtendedErrorCode::NotSupported => "Not Supported",
+            ExtendedErrorCode::NoReachableAuthority => "No Reachable Authority",
+            ExtendedErrorCode::NetworkError => "Network Error",
+            ExtendedErrorCode::InvalidData => "Invalid Data",
+            ExtendedErrorCode::SignatureExpiredBeforeValid => "Signature Expired before Valid",
+            ExtendedErrorCode::TooEarly => "Too Early",
+            ExtendedErrorCode::UnsupportedNsec3IterationsValue => "Unsupported NSEC3 Iterations Value",
+        };
+        write!(f, "{} ({})", u16::from(*self), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_rcodes_split_between_header_and_opt() {
+        assert_eq!(Rcode::BADCOOKIE.header_bits(), 7);
+        assert_eq!(Rcode::BADCOOKIE.extended_bits(), 1);
+        assert_eq!(Rcode::extended(7, 1), Rcode::BADCOOKIE);
+        assert_eq!(Rcode::extended(0x17, 1), Rcode::BADCOOKIE);
+        assert_eq!(Rcode::extended(7, 0), Rcode::YXRRSET);
+        assert_eq!(Rcode::extended(0, 1), Rcode::BADVERS);
+        assert_eq!((Rcode::NXDOMAIN.header_bits(), Rcode::NXDOMAIN.extended_bits()), (3, 0));
+        assert_eq!(Rcode::extended(0xf, 0xff), Rcode::Unassigned(4095));
+        assert_eq!(Rcode::BADCOOKIE.to_string(), "BADCOOKIE");
+        assert_eq!(Rcode::Unassigned(4095).to_string(), "RESERVED4095");
+    }
+
+    #[test]
+    fn every_type_and_class_value_survives_a_round_trip() {
+        for value in 0..=u16::MAX {
+            assert_eq!(u16::from(Kind::from(value)), value);
+            assert_eq!(u16::from(Class::from(value)), value);
+        }
+        assert_eq!(Kind::from(65534), Kind::Unknown(65534));
+        assert_eq!(Kind::from(65534).to_string(), "TYPE65534");
+        assert_eq!(Class::from(65280), Class::Unknown(65280));
+        assert_eq!(Class::from(65280).to_string(), "CLASS65280");
+        assert_eq!(Kind::from(15), Kind::MX);
+    }
+
+    #[test]
+    fn meta_types_and_classes_use_their_assigned_values() {
+        let kinds = [
+            (Kind::OPT, 41), (Kind::IXFR, 251), (Kind::AXFR, 252), (Kind::MAILB, 253), (Kind::MAILA, 254), (Kind::ANY, 255),
+        ];
+        for (kind, value) in kinds {
+            assert_eq!(u16::from(kind), value);
+            assert_eq!(Kind::from(value), kind);
+            assert!(kind.is_meta());
+        }
+        assert!(Kind::AXFR.is_qtype_only() && !Kind::OPT.is_qtype_only());
+        assert!(!Kind::A.is_meta() && !Kind::Unknown(65534).is_meta());
+        assert_eq!((u16::from(Class::NONE), u16::from(Class::ANY)), (254, 255));
+    }
+
+    #[test]
+    fn qtypes_and_qclasses_match_records() {
+        assert!(Kind::ANY.matches(Kind::A) && Kind::ANY.matches(Kind::Unknown(65534)));
+        assert!(!Kind::ANY.matches(Kind::OPT));
+        assert!(Kind::MAILB.matches(Kind::MG) && !Kind::MAILB.matches(Kind::MX));
+        assert!(Kind::MAILA.matches(Kind::MF) && !Kind::MAILA.matches(Kind::MB));
+        assert!(Kind::MX.matches(Kind::MX) && !Kind::MX.matches(Kind::A));
+        assert!(Class::ANY.matches(Class::CH) && Class::IN.matches(Class::IN) && !Class::IN.matches(Class::CH));
+    }
+
+    #[test]
+    fn types_parse_from_mnemonics_and_generic_forms() {
+        for (text, kind) in [
+            ("A", Kind::A), ("mx", Kind::MX), ("Nsec3Param", Kind::NSEC3PARAM), ("any", Kind::ANY),
+            ("TYPE65534", Kind::Unknown(65534)), ("type15", Kind::MX), ("TYPE0", Kind::Unknown(0)), ("TYPE006", Kind::SOA),
+        ] {
+            assert_eq!(text.parse::<Kind>(), Ok(kind), "{text}");
+        }
+        for text in ["", "AA", "TYPE", "TYPE65536", "TYPE-1", "TYPE+1", "TYPE 1", "CLASS1", " A"] {
+            assert_eq!(text.parse::<Kind>(), Err(ParameterParseError { parameter: "type", value: text.to_string() }), "{text}");
+        }
+        assert_eq!("AA".parse::<Kind>().unwrap_err().to_string(), "unknown type \"AA\"");
+    }
+
+    #[test]
+    fn classes_parse_from_mnemonics_and_generic_forms() {
+        for (text, class) in [
+            ("IN", Class::IN), ("ch", Class::CH), ("Hs", Class::HS), ("NONE", Class::NONE), ("any", Class::ANY),
+            ("CLASS3", Class::CH), ("class65280", Class::Unknown(65280)),
+        ] {
+            assert_eq!(text.parse::<Class>(), Ok(class), "{text}");
+        }
+        for text in ["", "INTERNET", "CLASS", "CLASS65536", "CLASSx", "TYPE1"] {
+            assert_eq!(text.parse::<Class>(), Err(ParameterParseError { parameter: "class", value: text.to_string() }), "{text}");
+        }
+        assert_eq!("CLASS".parse::<Class>().unwrap_err().to_string(), "unknown class \"CLASS\"");
+    }
+}
@@ -1,19 +1,137 @@
-/// A type alias for a `Result` where the error type is a `String`.
-pub type ResolverResult<T> = Result<T, String>;
-
-/// A macro for creating errors with source file and line information.
-#[macro_export]
-macro_rules! transform_result {
-    ($result:expr) => {{
-        match $result {
-            Ok(value) => Ok(value),
-            Err(err) => Err(format!("({}:{}) {:?}", file!(), line!(), err))
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::Ipv4Addr;
+use crate::dns_structs::name::{Name, NameParseError};
+use crate::dns_structs::reader::DecodeError;
+use crate::dns_structs::writer::EncodeError;
+use crate::enums::Rcode;
+use crate::Kind;
+
+/// A type alias for a `Result` where the error type is an `Error`.
+pub type ResolverResult<T> = Result<T, Error>;
+
+/// The errors that can happen while building, sending or decoding DNS messages and while resolving names.
+#[derive(Debug)]
+pub enum Error {
+    /// A socket operation failed.
+    Io(io::Error),
+    /// The name server did not answer in time.
+    Timeout,
+    /// A message could not be decoded.
+    Decode(DecodeError),
+    /// A message could not be encoded.
+    Encode(EncodeError),
+    /// A domain name is not valid.
+    InvalidName(NameParseError),
+    /// The name server answered with an error response code other than NXDOMAIN.
+    ServerFailure(Rcode),
+    /// The queried name does not exist (NXDOMAIN).
+    NameError {
+        /// The name that does not exist.
+        name: Name,
+    },
+    /// The queried name exists but has no records of the queried type.
+    NoData {
+        /// The queried name.
+        name: Name,
+        /// The queried type.
+        kind: Kind,
+    },
+    /// The name server neither answered nor referred us to a server closer to the answer.
+    Lame {
+        /// The name server that gave the useless response.
+        name_server: Ipv4Addr,
+    },
+    /// No response arrived in time, and the responses that did arrive did not echo the client cookie
+    /// sent with the query, so they may be spoofed (RFC 7873).
+    CookieMismatch {
+        /// The name server the response claims to come from.
+        name_server: Ipv4Addr,
+    },
+    /// Too many referrals or nested name server lookups were needed to resolve a name.
+    MaxDepthExceeded,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Timeout => write!(f, "Timed out waiting for the name server"),
+            Error::Decode(err) => write!(f, "Cannot decode the message at offset {}: {}", err.offset, err.kind),
+            Error::Encode(err) => write!(f, "Cannot encode the message: {}", err),
+            Error::InvalidName(err) => write!(f, "Invalid domain name: {}", err),
+            Error::ServerFailure(rcode) => write!(f, "The name server answered {}", rcode),
+            Error::NameError { name } => write!(f, "{} does not exist (NXDOMAIN)", name),
+            Error::NoData { name, kind } => write!(f, "{} has no {} records", name, kind),
+            Error::Lame { name_server } => write!(f, "Lame response from {}", name_server),
+            Error::CookieMismatch { name_server } => write!(f, "No response from {} echoed our DNS cookie", name_server),
+            Error::MaxDepthExceeded => write!(f, "Too many referrals while resolving"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) => Some(err),
+            Error::Encode(err) => Some(err),
+            Error::InvalidName(err) => Some(err),
+            _ => None,
         }
-    }};
-    ($msg:expr, $result:expr) => {{
-        match $result {
-            Ok(value) => Ok(value),
-            Err(err) => Err(format!("({}:{}) {}: {:?}", file!(), line!(), $msg, err))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        Error::Decode(err)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(err: EncodeError) -> Self {
+        Error::Encode(err)
+    }
+}
+
+impl From<NameParseError> for Error {
+    fn from(err: NameParseError) -> Self {
+        Error::InvalidName(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+    use crate::dns_structs::reader::DecodeErrorKind;
+
+    #[test]
+    fn sources_are_kept() {
+        let error = Error::from(DecodeError::new(12, DecodeErrorKind::UnexpectedEof));
+        assert_eq!(error.to_string(), "Cannot decode the message at offset 12: unexpected end of message");
+        let source = error.source().and_then(|source| source.downcast_ref::<DecodeError>()).unwrap();
+        assert_eq!(source.offset, 12);
+
+        let error = Error::from(EncodeError::BufferTooSmall(12));
+        assert!(error.source().is_some_and(|source| source.is::<EncodeError>()));
+        let error = Error::from("a..b".parse::<Name>().unwrap_err());
+        assert!(error.source().is_some_and(|source| source.is::<NameParseError>()));
+        assert!(Error::Timeout.source().is_none());
+    }
+
+    #[test]
+    fn io_errors_stay_io_errors() {
+        for kind in [io::ErrorKind::TimedOut, io::ErrorKind::WouldBlock, io::ErrorKind::AddrInUse] {
+            let error = Error::from(io::Error::from(kind));
+            assert!(matches!(&error, Error::Io(err) if err.kind() == kind));
+            assert!(error.source().is_some_and(|source| source.is::<io::Error>()));
         }
-    }};
+    }
 }
@@ -1,9 +1,18 @@
-use std::net::{Ipv4Addr, UdpSocket};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+use crate::dns_structs::edns::{Edns, EdnsOption};
 use crate::dns_structs::header::{DNSHeader, Flags};
+use crate::dns_structs::message_ref::DnsMessageRef;
+use crate::dns_structs::name::Name;
 use crate::dns_structs::packet::DNSPacket;
-use crate::dns_structs::question::DNSQuestion;
+use crate::dns_structs::reader::MAX_NAME_LEN;
 use crate::dns_structs::record::Data;
-use crate::enums::{Class, Flag};
+use crate::dns_structs::writer::{EncodeError, SliceWriter};
+use crate::enums::{Class, Flag, Rcode};
+use crate::errors::{Error, ResolverResult};
 use clap::ValueEnum;
 pub use crate::enums::Kind;
 
@@ -23,61 +32,387 @@ pub enum LogLevel {
 #[derive(Debug, Clone)]
 pub struct QueryArgs {
     /// The domain name to query.
-    pub domain_name: String,
+    pub domain_name: Name,
     /// The IPv4 address of the name server to send the query to.
     pub name_server: Ipv4Addr,
+    /// The local address and port queries are sent from. Port 0 lets the operating system pick a
+    /// different ephemeral port for every query, which it randomizes (RFC 6056), so that together
+    /// with the random query ID an off-path attacker has to guess about 32 bits to spoof a response.
+    pub source: SocketAddrV4,
     /// The type of DNS record to query.
     pub record_type: Kind,
     /// An array of flags to include in the query.
     pub flags: Vec<Flag>,
     /// Whether to print debug information.
     pub log_level: LogLevel,
+    /// The EDNS parameters sent with the query, including the advertised UDP payload size, or
+    /// `None` to send a plain DNS query that limits responses to 512 bytes.
+    pub edns: Option<Edns>,
+    /// Whether to send DNS cookies with the queries that use EDNS and check that responses echo them (RFC 7873).
+    pub cookies: bool,
+    /// How long to wait for the response to the first transmission of a query. Every
+    /// retransmission waits twice as long as the previous one.
+    pub timeout: Duration,
+    /// How many times a query is retransmitted when no response arrives in time.
+    pub retries: u32,
+    /// How long the whole resolution may take, including referrals and retransmissions, or `None`
+    /// for no limit beyond the timeouts of every query.
+    pub deadline: Option<Duration>,
+    /// Whether to randomize the case of the letters of the query name and only accept responses
+    /// that echo it exactly (DNS 0x20), which adds about one bit per letter for a spoofer to guess.
+    /// Servers found not to preserve the case are queried without it for the rest of the resolution.
+    pub randomize_case: bool,
 }
 
-/// Builds a DNS query for the given domain name and record type.
+/// The time `QueryArgs::timeout` is usually set to.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The number of retransmissions `QueryArgs::retries` is usually set to.
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// The largest query `encode_query` can produce without EDNS: a header and a question with a 255
+/// byte name. Queries with EDNS take `Edns::record_len` more bytes.
+pub const MAX_QUERY_LEN: usize = DNSHeader::LEN + MAX_NAME_LEN + 4;
+
+/// The largest response accepted over UDP when no EDNS payload size is advertised (RFC 1035 section 4.2.1).
+const MIN_UDP_PAYLOAD_SIZE: u16 = 512;
+
+/// The port name servers listen on.
+const DNS_PORT: u16 = 53;
+
+/// Encodes a DNS query for the given domain name and record type into `buffer`.
+///
+/// Nothing is allocated, so the same buffer can be reused for any number of queries. A buffer of
+/// `MAX_QUERY_LEN` bytes fits any query without EDNS. When `args.edns` is set an OPT record is
+/// added to the additional section.
 ///
 /// # Arguments
 ///
+/// * `id` - The identifier of the query.
 /// * `args` - A `QueryArgs` structure containing the arguments for the query.
+/// * `buffer` - The buffer to write the query to.
 ///
 /// # Returns
 ///
-/// A `Vec<u8>` containing the bytes of the DNS query.
-fn build_query(args: &QueryArgs) -> Vec<u8> {
-    let id = rand::random();
-    let mut query = DNSHeader {
+/// The number of bytes written at the start of `buffer`.
+pub fn encode_query(id: u16, args: &QueryArgs, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+    let header = DNSHeader {
         id,
         flags: Flags::compose(&args.flags),
         num_questions: 1,
+        num_additionals: args.edns.is_some() as u16,
         ..DNSHeader::default()
-    }.to_bytes();
-    query.extend_from_slice(&DNSQuestion {
-        name: args.domain_name.to_string(),
-        kind: args.record_type,
-        class: Class::IN,
-    }.to_bytes());
-    return query;
+    };
+    let mut writer = SliceWriter::new(buffer);
+    header.encode_into(&mut writer)?;
+    writer.write_name(&args.domain_name)?;
+    writer.write_u16(u16::from(args.record_type))?;
+    writer.write_u16(u16::from(Class::IN))?;
+    if let Some(edns) = &args.edns {
+        edns.encode_into(&mut writer)?;
+    }
+    Ok(writer.len())
+}
+
+/// Builds a DNS query for the given domain name and record type.
+///
+/// # Arguments
+///
+/// * `args` - A `QueryArgs` structure containing the arguments for the query.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the bytes of the DNS query.
+pub fn build_query(args: &QueryArgs) -> Result<Vec<u8>, EncodeError> {
+    let mut query = vec![0; query_len(args)];
+    let len = encode_query(rand::random(), args, &mut query)?;
+    query.truncate(len);
+    Ok(query)
+}
+
+/// Returns the length of the buffer needed to encode the query for `args`.
+fn query_len(args: &QueryArgs) -> usize {
+    MAX_QUERY_LEN + args.edns.as_ref().map_or(0, Edns::record_len)
+}
+
+/// The DNS cookies exchanged with the name servers during a resolution (RFC 7873).
+///
+/// Every server gets its own random client cookie, and the server cookie it answers with is sent
+/// back in the following queries to it.
+#[derive(Debug, Default)]
+struct CookieJar {
+    /// The client cookie and the last server cookie of every name server.
+    servers: HashMap<Ipv4Addr, ([u8; 8], Option<Vec<u8>>)>,
+}
+
+impl CookieJar {
+    /// Returns the COOKIE option for a query to `name_server`.
+    fn option(&mut self, name_server: Ipv4Addr) -> EdnsOption {
+        let (client, server) = self.servers.entry(name_server).or_insert_with(|| (rand::random(), None));
+        EdnsOption::Cookie { client: *client, server: server.clone() }
+    }
+
+    /// Remembers the server cookie of a response from `name_server`, which `mismatch` has already
+    /// checked echoes the client cookie. Returns the COOKIE option of the response.
+    fn remember(&mut self, name_server: Ipv4Addr, response: &DNSPacket) -> Option<EdnsOption> {
+        let option = response.edns().into_iter()
+            .flat_map(|edns| edns.options)
+            .find(|option| matches!(option, EdnsOption::Cookie { .. }));
+        let cookies = self.servers.get_mut(&name_server);
+        if let (Some(EdnsOption::Cookie { server: Some(server), .. }), Some(cookies)) = (&option, cookies) {
+            cookies.1 = Some(server.clone());
+        }
+        option
+    }
+}
+
+/// The state shared by all the queries of a resolution.
+#[derive(Debug)]
+struct Resolution {
+    cookies: CookieJar,
+    /// When the resolution must be over, see `QueryArgs::deadline`.
+    deadline: Option<Instant>,
+    /// How many datagrams were discarded because they did not answer the outstanding query.
+    discarded: usize,
+    /// The name servers that do not preserve the case of the query name, see `QueryArgs::randomize_case`.
+    case_insensitive: HashSet<Ipv4Addr>,
+}
+
+impl Resolution {
+    fn new(args: &QueryArgs) -> Self {
+        Self {
+            cookies: CookieJar::default(),
+            deadline: args.deadline.map(|deadline| Instant::now() + deadline),
+            discarded: 0,
+            case_insensitive: HashSet::new(),
+        }
+    }
 }
 
 /// Sends a DNS query to the given name server and returns the response.
 ///
+/// The response is read into a buffer of the UDP payload size advertised with EDNS, or of 512
+/// bytes without EDNS. When cookies are enabled the query carries the cookies of the resolution for
+/// the server, and responses that do not echo the client cookie are discarded.
+///
+/// The query is retransmitted with exponential backoff until a response arrives, failing once the
+/// retries or the deadline of the resolution run out. Datagrams that do not answer the query (see
+/// `mismatch`) are discarded and counted while waiting.
+///
+/// With `QueryArgs::randomize_case` the response must echo the case of the query name. As soon as
+/// a response only differs in the case, the server is taken not to preserve the case and is queried
+/// again without it from the same socket.
+///
 /// # Arguments
 ///
 /// * `args` - A `QueryArgs` structure containing the arguments for the query.
+/// * `server` - The address of `args.name_server` to send the query to.
 ///
 /// # Returns
 ///
-/// A `Result` containing either a `DNSPacket` representing the response or an error message.
-fn send_query(args: &QueryArgs) -> Result<DNSPacket, String> {
-    let query = build_query(args);
-    let socket = transform_result!(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 12345)))?;
-    transform_result!(socket.connect((args.name_server, 53)))?;
-    transform_result!(socket.send(&query))?;
-    let mut answer = vec![0; 2048];
-    let _ = transform_result!(socket.recv(&mut answer))?;
-    DNSPacket::try_from(answer)
+/// A `Result` containing either a `DNSPacket` representing the response or an error.
+fn send_query(args: &QueryArgs, server: SocketAddr, resolution: &mut Resolution) -> ResolverResult<DNSPacket> {
+    let cookie = (args.cookies && args.edns.is_some()).then(|| resolution.cookies.option(args.name_server));
+    if let Some(cookie) = cookie.as_ref().filter(|_| args.log_level as u8 >= LogLevel::Debug as u8) {
+        println!("Sending {} to {}", cookie, args.name_server);
+    }
+    let randomize_case = args.randomize_case && !resolution.case_insensitive.contains(&args.name_server);
+    let query_args = &QueryArgs {
+        domain_name: if randomize_case { args.domain_name.randomize_case() } else { args.domain_name.clone() },
+        randomize_case,
+        edns: args.edns.clone().map(|mut edns| {
+            edns.options.extend(cookie.clone());
+            edns
+        }),
+        ..args.clone()
+    };
+    let mut query = vec![0; query_len(query_args)];
+    let id = rand::random();
+    let len = encode_query(id, query_args, &mut query)?;
+    let socket = UdpSocket::bind(args.source)?;
+    if args.log_level as u8 >= LogLevel::Debug as u8 {
+        println!("Sending the query from {}", socket.local_addr()?);
+    }
+    let payload_size = query_args.edns.as_ref()
+        .map_or(MIN_UDP_PAYLOAD_SIZE, |edns| edns.udp_payload_size.max(MIN_UDP_PAYLOAD_SIZE));
+    let mut answer = vec![0; payload_size as usize];
+    let len = match exchange(&socket, &query[..len], id, &mut answer, server, query_args, resolution)? {
+        Some(len) => len,
+        None => {
+            if args.log_level as u8 >= LogLevel::Info as u8 {
+                println!("{} does not preserve the case of the query name, retrying without 0x20", args.name_server);
+            }
+            let query_args = &QueryArgs { domain_name: args.domain_name.clone(), randomize_case: false, ..query_args.clone() };
+            let id = rand::random();
+            let len = encode_query(id, query_args, &mut query)?;
+            // Without 0x20 no response can differ only in the case.
+            exchange(&socket, &query[..len], id, &mut answer, server, query_args, resolution)?.ok_or(Error::Timeout)?
+        }
+    };
+    answer.truncate(len);
+    let response = DNSPacket::try_from(answer)?;
+    if cookie.is_some() {
+        let received = resolution.cookies.remember(args.name_server, &response);
+        if args.log_level as u8 >= LogLevel::Debug as u8 {
+            match received {
+                Some(cookie) => println!("Received {} from {}", cookie, args.name_server),
+                None => println!("No cookie received from {}", args.name_server),
+            }
+        }
+    }
+    Ok(response)
 }
 
+/// Sends `query`, whose ID is `id`, on `socket` to `server` and receives the response into `answer`,
+/// returning its length. The query is sent again every time the timeout expires, doubling the timeout each
+/// time. Datagrams that arrive in the meantime but do not answer the query are discarded.
+///
+/// Returns `None` as soon as a datagram only differs from the query in the case of the name, after
+/// recording the server as not preserving the case. Fails with `Error::CookieMismatch` when no
+/// response arrives in time but some datagram was discarded for not echoing the client cookie, and
+/// with `Error::Timeout` otherwise.
+fn exchange(
+    socket: &UdpSocket,
+    query: &[u8],
+    id: u16,
+    answer: &mut [u8],
+    server: SocketAddr,
+    args: &QueryArgs,
+    resolution: &mut Resolution,
+) -> ResolverResult<Option<usize>> {
+    let mut timeout = args.timeout;
+    let mut cookie_mismatch = false;
+    for attempt in 0..=args.retries {
+        let remaining = resolution.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.is_some_and(|remaining| remaining.is_zero()) {
+            break;
+        }
+        let wait = remaining.map_or(timeout, |remaining| remaining.min(timeout));
+        if attempt > 0 && args.log_level as u8 >= LogLevel::Info as u8 {
+            println!("No response from {}, retransmitting (attempt {} of {})", server, attempt + 1, args.retries + 1);
+        }
+        socket.send_to(query, server)?;
+        let expires = Instant::now() + wait;
+        loop {
+            let left = expires.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(left))?;
+            match socket.recv_from(answer) {
+                Ok((len, from)) => match mismatch(&answer[..len], from, server, id, args) {
+                    None => return Ok(Some(len)),
+                    Some(reason) => {
+                        resolution.discarded += 1;
+                        cookie_mismatch |= matches!(reason, Mismatch::Cookie);
+                        if args.log_level as u8 >= LogLevel::Info as u8 {
+                            println!("Discarded a datagram from {}: {}", from, reason);
+                        }
+                        if matches!(reason, Mismatch::Case) {
+                            resolution.case_insensitive.insert(args.name_server);
+                            return Ok(None);
+                        }
+                    }
+                },
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        timeout = timeout.saturating_mul(2);
+    }
+    if cookie_mismatch {
+        return Err(Error::CookieMismatch { name_server: args.name_server });
+    }
+    Err(Error::Timeout)
+}
+
+/// Why a datagram is not the response to the outstanding query.
+enum Mismatch {
+    /// The response has a COOKIE option that does not echo the client cookie of the query, so it may
+    /// be spoofed (RFC 7873 section 5.3).
+    Cookie,
+    /// The question of the response only differs from the query in the case of the name, which was
+    /// randomized (see `QueryArgs::randomize_case`).
+    Case,
+    /// Any other reason, described for the logs.
+    Other(String),
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Cookie => write!(f, "the client cookie does not match ours"),
+            Mismatch::Case => write!(f, "the question name does not echo the case of the query"),
+            Mismatch::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Returns why the datagram `response` received from `from` is not the response of `server` to the
+/// query with ID `id` for `args`, or `None` if it is.
+///
+/// The response must carry the ID of the query, have the QR bit set and echo its question. Servers
+/// that cannot parse a query may answer FORMERR without a question section, which is accepted.
+/// With `QueryArgs::randomize_case` the question name must also have the same case as the query.
+/// When the query has a COOKIE option, a COOKIE option in the response must echo its client cookie.
+fn mismatch(response: &[u8], from: SocketAddr, server: SocketAddr, id: u16, args: &QueryArgs) -> Option<Mismatch> {
+    if from != server {
+        return Some(Mismatch::Other(format!("expected a response from {}", server)));
+    }
+    let message = match DnsMessageRef::new(response) {
+        Ok(message) => message,
+        Err(err) => return Some(Mismatch::Other(err.to_string())),
+    };
+    let header = message.header();
+    if header.id() != id {
+        return Some(Mismatch::Other(format!("ID {} does not match the query ID {}", header.id(), id)));
+    }
+    if !header.is_response() {
+        return Some(Mismatch::Other("the QR bit is not set".to_string()));
+    }
+    let mut questions = message.questions();
+    match (questions.next(), questions.next()) {
+        (None, None) if header.rcode() == Rcode::FORMERR => {}
+        (Some(Ok(question)), None)
+            if question.name == args.domain_name && question.kind == args.record_type && question.class == Class::IN => {
+            if args.randomize_case && !question.name.eq_case_sensitive(&args.domain_name) {
+                return Some(Mismatch::Case);
+            }
+        }
+        _ => return Some(Mismatch::Other("the question section does not match the query".to_string())),
+    }
+    // Without a client cookie in the query there is nothing more to check.
+    let sent = args.edns.iter().flat_map(|edns| &edns.options).find_map(|option| match option {
+        EdnsOption::Cookie { client, .. } => Some(client),
+        _ => None,
+    })?;
+    for record in message.additionals() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => return Some(Mismatch::Other(err.to_string())),
+        };
+        if record.kind != Kind::OPT {
+            continue;
+        }
+        let options = match record.data() {
+            Ok(Data::Opt(options)) => options,
+            Ok(_) => continue,
+            Err(err) => return Some(Mismatch::Other(err.to_string())),
+        };
+        let echoed = options.into_iter().find_map(|option| match option {
+            EdnsOption::Cookie { client, .. } => Some(client),
+            _ => None,
+        });
+        if echoed.is_some_and(|echoed| echoed != *sent) {
+            return Some(Mismatch::Cookie);
+        }
+    }
+    None
+}
+
+/// The maximum number of referrals and nested name server lookups followed while resolving a name.
+const MAX_DEPTH: usize = 32;
+
 /// Resolves the given domain name to an IPv4 address using the given name server and record type.
 ///
 /// # Arguments
@@ -86,23 +421,79 @@ fn send_query(args: &QueryArgs) -> Result<DNSPacket, String> {
 ///
 /// # Returns
 ///
-/// A `Result` containing either an `Ipv4Addr` representing the resolved IP address or an error message.
-pub fn resolve(args: &QueryArgs) -> Result<Vec<Data>, String> {
+/// A `Result` containing either the data of the matching answers or an error.
+pub fn resolve(args: &QueryArgs) -> ResolverResult<Vec<Data>> {
+    Ok(resolve_response(args)?.response.get_answers(args.record_type))
+}
+
+/// The outcome of a successful resolution.
+#[derive(Debug)]
+pub struct Resolved {
+    /// The response holding the answers.
+    pub response: DNSPacket,
+    /// How many datagrams were discarded during the resolution because they did not answer the
+    /// outstanding query, which may be a sign of spoofing attempts.
+    pub discarded: usize,
+}
+
+/// Resolves the given domain name like `resolve` does, but returns the whole response holding the
+/// answers, for callers that want its EDNS options or other sections, along with diagnostics.
+///
+/// DNS cookies are only remembered for the duration of the call.
+pub fn resolve_response(args: &QueryArgs) -> ResolverResult<Resolved> {
+    let mut resolution = Resolution::new(args);
+    let response = resolve_with_depth(args, 0, &mut resolution)?;
+    Ok(Resolved { response, discarded: resolution.discarded })
+}
+
+fn resolve_with_depth(args: &QueryArgs, mut depth: usize, resolution: &mut Resolution) -> ResolverResult<DNSPacket> {
     let mut args = args.clone();
     loop {
+        depth += 1;
+        if depth > MAX_DEPTH {
+            return Err(Error::MaxDepthExceeded);
+        }
         if args.log_level as u8 >= LogLevel::Info as u8 {
             println!("Querying {} for {}", &args.name_server, args.domain_name);
         }
         if args.log_level as u8 >= LogLevel::Debug as u8 {
             println!("{args:?}");
         }
-        let response = send_query(&args)?;
+        let server = SocketAddr::from((args.name_server, DNS_PORT));
+        let mut response = send_query(&args, server, resolution)?;
+        // A FORMERR without an OPT record means the server does not understand EDNS (RFC 6891 section 7).
+        if response.rcode() == Rcode::FORMERR && args.edns.is_some() && response.edns().is_none() {
+            if args.log_level as u8 >= LogLevel::Info as u8 {
+                println!("{} does not support EDNS, retrying without it", &args.name_server);
+            }
+            response = send_query(&QueryArgs { edns: None, ..args.clone() }, server, resolution)?;
+        }
+        // BADCOOKIE comes with a fresh server cookie, which the retry sends back (RFC 7873 section 5.3).
+        if response.rcode() == Rcode::BADCOOKIE && args.cookies {
+            if args.log_level as u8 >= LogLevel::Info as u8 {
+                println!("{} rejected our server cookie, retrying with a new one", &args.name_server);
+            }
+            response = send_query(&args, server, resolution)?;
+        }
         if args.log_level as u8 >= LogLevel::Debug as u8 {
-            println!("{response:#?}");
+            println!("{response}");
         }
-        let answers = response.get_answers(args.record_type);
-        if !answers.is_empty() {
-            return Ok(answers)
+        let rcode = response.rcode();
+        if args.log_level as u8 >= LogLevel::Info as u8 {
+            let extended_errors = response.edns().into_iter()
+                .flat_map(|edns| edns.options)
+                .filter(|option| matches!(option, EdnsOption::ExtendedError { .. }))
+                .map(|option| format!(", {}", option))
+                .collect::<String>();
+            println!("Status from {}: {}{}", &args.name_server, rcode, extended_errors);
+        }
+        match rcode {
+            Rcode::NOERROR => {}
+            Rcode::NXDOMAIN => return Err(Error::NameError { name: args.domain_name }),
+            rcode => return Err(Error::ServerFailure(rcode)),
+        }
+        if response.answers.iter().any(|answer| args.record_type.matches(answer.kind)) {
+            return Ok(response)
         } else if let Some(ip) = response.get_name_server_ip() {
             args.name_server = ip;
         } else if let Some(domain_name) = response.get_name_server() {
@@ -111,13 +502,337 @@ pub fn resolve(args: &QueryArgs) -> Result<Vec<Data>, String> {
                 record_type: Kind::A,
                 ..args.clone()
             };
-            let result = resolve(&new_args)?;
+            let result = resolve_with_depth(&new_args, depth, resolution)?.get_answers(Kind::A);
             args.name_server = match result.into_iter().next() {
                 Some(Data::IPv4(ip)) => ip,
-                _ => { return transform_result!(Err("This will never happens")) }
+                _ => return Err(Error::Lame { name_server: args.name_server }),
             };
+        } else if response.header.is_set(Flag::AA) || response.authorities.iter().any(|record| record.kind == Kind::SOA) {
+            return Err(Error::NoData { name: args.domain_name, kind: args.record_type })
         } else {
-            return Err(format!("Cannot resolve {} on server {}", &args.domain_name, &args.name_server))
+            return Err(Error::Lame { name_server: args.name_server })
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(domain_name: &str) -> QueryArgs {
+        QueryArgs {
+            domain_name: domain_name.parse().unwrap(),
+            name_server: Ipv4Addr::LOCALHOST,
+            source: SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0),
+            record_type: Kind::A,
+            flags: vec![Flag::RD],
+            log_level: LogLevel::None,
+            edns: None,
+            cookies: false,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            deadline: None,
+            randomize_case: false,
+        }
+    }
+
+    #[test]
+    fn encodes_queries_into_exactly_sized_buffers() {
+        let plain = args("example.com");
+        let edns = Edns { options: vec![EdnsOption::Padding(4)], ..Edns::default() };
+        let with_edns = QueryArgs { edns: Some(edns), ..plain.clone() };
+        for args in [plain, with_edns] {
+            let query = build_query(&args).unwrap();
+            let mut buffer = vec![0; query.len()];
+            let id = u16::from_be_bytes([query[0], query[1]]);
+            assert_eq!(encode_query(id, &args, &mut buffer).unwrap(), query.len());
+            assert_eq!(buffer, query);
+            for len in 0..query.len() {
+                let mut buffer = vec![0; len];
+                assert!(matches!(encode_query(id, &args, &mut buffer), Err(EncodeError::BufferTooSmall(size)) if size == len));
+            }
+        }
+    }
+
+    #[test]
+    fn max_query_len_fits_the_longest_name() {
+        let label = "a".repeat(63);
+        let args = args(&[label.as_str(), &label, &label, &"b".repeat(61)].join("."));
+        assert_eq!(args.domain_name.wire().len(), MAX_NAME_LEN);
+        let mut buffer = [0; MAX_QUERY_LEN];
+        assert_eq!(encode_query(1, &args, &mut buffer).unwrap(), MAX_QUERY_LEN);
+        let query = DNSPacket::try_from(&buffer[..]).unwrap();
+        assert_eq!(query.questions[0].to_bytes()[..MAX_NAME_LEN], *args.domain_name.wire());
+    }
+
+    #[test]
+    fn adds_an_opt_record_to_queries_with_edns() {
+        let edns = Edns {
+            udp_payload_size: 1232,
+            dnssec_ok: true,
+            options: vec![EdnsOption::Nsid(Vec::new())],
+            ..Edns::default()
+        };
+        let args = QueryArgs { edns: Some(edns.clone()), ..args("example.com") };
+        let mut buffer = vec![0; query_len(&args)];
+        let len = encode_query(0x1234, &args, &mut buffer).unwrap();
+        let expected = [
+            &b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x01"[..],
+            b"\x07example\x03com\x00\x00\x01\x00\x01",
+            b"\x00\x00\x29\x04\xd0\x00\x00\x80\x00\x00\x04\x00\x03\x00\x00",
+        ].concat();
+        assert_eq!(buffer[..len], expected);
+        assert_eq!(len, DNSHeader::LEN + 17 + edns.record_len());
+
+        let query = DNSPacket::try_from(&buffer[..len]).unwrap();
+        assert_eq!(query.edns(), Some(edns));
+        assert_eq!(query.additionals[0].kind, Kind::OPT);
+    }
+
+    /// A response carrying the given EDNS options.
+    fn response_with(options: Vec<EdnsOption>) -> DNSPacket {
+        let mut response = DNSPacket {
+            header: DNSHeader { flags: Flags(0x8180), ..DNSHeader::default() },
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        };
+        response.set_edns(Some(Edns { options, ..Edns::default() }));
+        response
+    }
+
+    #[test]
+    fn cookie_jar_keeps_a_client_cookie_per_server() {
+        let (first, second) = (Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2));
+        let mut jar = CookieJar::default();
+        let EdnsOption::Cookie { client, server: None } = jar.option(first) else {
+            panic!("the first query to a server should only carry a client cookie");
+        };
+        assert_eq!(jar.option(first), EdnsOption::Cookie { client, server: None });
+        let EdnsOption::Cookie { client: other, server: None } = jar.option(second) else {
+            panic!("the first query to a server should only carry a client cookie");
+        };
+        assert_ne!(other, client);
+    }
+
+    #[test]
+    fn remembered_server_cookies_go_back_to_their_server_only() {
+        let (first, second) = (Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2));
+        let mut jar = CookieJar::default();
+        let EdnsOption::Cookie { client, .. } = jar.option(first) else { unreachable!() };
+        let EdnsOption::Cookie { client: other, .. } = jar.option(second) else { unreachable!() };
+
+        let cookie = EdnsOption::Cookie { client, server: Some(vec![7; 16]) };
+        let response = response_with(vec![EdnsOption::Padding(4), cookie.clone()]);
+        assert_eq!(jar.remember(first, &response), Some(cookie.clone()));
+        assert_eq!(jar.option(first), cookie);
+        assert_eq!(jar.option(second), EdnsOption::Cookie { client: other, server: None });
+
+        // A newer server cookie replaces the old one, and a response without one keeps it.
+        let cookie = EdnsOption::Cookie { client, server: Some(vec![8; 8]) };
+        jar.remember(first, &response_with(vec![cookie.clone()]));
+        assert_eq!(jar.option(first), cookie);
+        assert_eq!(jar.remember(first, &response_with(Vec::new())), None);
+        assert!(jar.remember(first, &response_with(vec![EdnsOption::Cookie { client, server: None }])).is_some());
+        assert_eq!(jar.option(first), cookie);
+
+        // Responses from servers that were never queried are not remembered.
+        let third = Ipv4Addr::new(192, 0, 2, 3);
+        jar.remember(third, &response_with(vec![EdnsOption::Cookie { client, server: Some(vec![9; 8]) }]));
+        assert!(matches!(jar.option(third), EdnsOption::Cookie { server: None, .. }));
+    }
+
+    /// Binds a client and a server socket on the loopback interface.
+    fn loopback() -> (UdpSocket, UdpSocket) {
+        let bind = || UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        (bind(), bind())
+    }
+
+    /// Encodes the query for `args`, returning it along with its ID.
+    fn query(args: &QueryArgs) -> (Vec<u8>, u16) {
+        let query = build_query(args).unwrap();
+        let id = u16::from_be_bytes([query[0], query[1]]);
+        (query, id)
+    }
+
+    /// Turns a query into a response without records, echoing its ID and question.
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] |= 0x80;
+        response
+    }
+
+    #[test]
+    fn retransmits_when_a_datagram_is_lost() {
+        let (client, server) = loopback();
+        let server_addr = server.local_addr().unwrap();
+        let args = QueryArgs { timeout: Duration::from_millis(100), ..args("example.com") };
+        let (query, id) = query(&args);
+        let handle = std::thread::spawn(move || {
+            let mut buffer = [0; 512];
+            // The first transmission is dropped.
+            let (len, _) = server.recv_from(&mut buffer).unwrap();
+            let first = buffer[..len].to_vec();
+            let (len, from) = server.recv_from(&mut buffer).unwrap();
+            assert_eq!(buffer[..len], first);
+            server.send_to(&answer(&buffer[..len]), from).unwrap();
+        });
+        let mut resolution = Resolution::new(&args);
+        let mut buffer = [0; 512];
+        let len = exchange(&client, &query, id, &mut buffer, server_addr, &args, &mut resolution).unwrap().unwrap();
+        handle.join().unwrap();
+        assert_eq!(buffer[..len], answer(&query));
+        assert_eq!(resolution.discarded, 0);
+    }
+
+    #[test]
+    fn the_deadline_cuts_the_timeout_short() {
+        let (client, server) = loopback();
+        let deadline = Duration::from_millis(200);
+        let args = QueryArgs { timeout: Duration::from_secs(5), deadline: Some(deadline), ..args("example.com") };
+        let (query, id) = query(&args);
+        let mut resolution = Resolution::new(&args);
+        let start = Instant::now();
+        let result = exchange(&client, &query, id, &mut [0; 512], server.local_addr().unwrap(), &args, &mut resolution);
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(start.elapsed() < args.timeout, "{:?}", start.elapsed());
+        assert!(resolution.deadline.is_some_and(|deadline| Instant::now() >= deadline));
+    }
+
+    /// Applies `change` to a copy of `response`.
+    fn altered(response: &[u8], change: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut response = response.to_vec();
+        change(&mut response);
+        response
+    }
+
+    #[test]
+    fn mismatch_checks_the_source_id_and_flags() {
+        let args = args("example.com");
+        let (query, id) = query(&args);
+        let response = answer(&query);
+        let server = SocketAddr::from((Ipv4Addr::LOCALHOST, 53));
+        let check = |response: &[u8], from| mismatch(response, from, server, id, &args).map(|reason| reason.to_string());
+        assert_eq!(check(&response, server), None);
+        for from in [SocketAddr::from((Ipv4Addr::LOCALHOST, 5353)), SocketAddr::from((Ipv4Addr::new(127, 0, 0, 2), 53))] {
+            assert_eq!(check(&response, from).unwrap(), "expected a response from 127.0.0.1:53");
+        }
+        let wrong_id = altered(&response, |response| response[1] ^= 1);
+        assert_eq!(check(&wrong_id, server).unwrap(), format!("ID {} does not match the query ID {}", id ^ 1, id));
+        assert_eq!(check(&query, server).unwrap(), "the QR bit is not set");
+        assert!(check(&response[..20], server).is_some());
+    }
+
+    #[test]
+    fn mismatch_checks_the_question() {
+        let args = args("example.com");
+        let (query, id) = query(&args);
+        let response = answer(&query);
+        let server = SocketAddr::from((Ipv4Addr::LOCALHOST, 53));
+        let check = |response: &[u8]| mismatch(response, server, server, id, &args).map(|reason| reason.to_string());
+        let changes: [fn(&mut Vec<u8>); 4] = [
+            // QNAME xample.com.
+            |response| response[13] = b'x',
+            // QTYPE AAAA
+            |response| response[26] = 28,
+            // QCLASS CH
+            |response| response[28] = 3,
+            // No question in a NOERROR response.
+            |response| {
+                response[5] = 0;
+                response.truncate(DNSHeader::LEN);
+            },
+        ];
+        for change in changes {
+            assert_eq!(check(&altered(&response, change)).unwrap(), "the question section does not match the query");
         }
+        // A server that cannot parse the query may leave the question out of its FORMERR.
+        let formerr = altered(&response, |response| {
+            response[3] |= 1;
+            response[5] = 0;
+            response.truncate(DNSHeader::LEN);
+        });
+        assert_eq!(check(&formerr), None);
+        // Case differences only matter when the case was randomized.
+        assert_eq!(check(&altered(&response, |response| response[13] = b'E')), None);
+    }
+
+    #[test]
+    fn counts_the_discarded_datagrams() {
+        let (client, server) = loopback();
+        let server_addr = server.local_addr().unwrap();
+        let args = QueryArgs { timeout: Duration::from_secs(5), ..args("example.com") };
+        let (query, id) = query(&args);
+        let handle = std::thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let (len, from) = server.recv_from(&mut buffer).unwrap();
+            let response = answer(&buffer[..len]);
+            let spoofs = [
+                altered(&response, |response| response[0] ^= 1),
+                buffer[..len].to_vec(),
+                altered(&response, |response| response[26] = 28),
+            ];
+            for spoof in spoofs {
+                server.send_to(&spoof, from).unwrap();
+            }
+            // The right response from the wrong address.
+            UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().send_to(&response, from).unwrap();
+            server.send_to(&response, from).unwrap();
+        });
+        let mut resolution = Resolution::new(&args);
+        let mut buffer = [0; 512];
+        let len = exchange(&client, &query, id, &mut buffer, server_addr, &args, &mut resolution).unwrap().unwrap();
+        handle.join().unwrap();
+        assert_eq!(buffer[..len], answer(&query));
+        assert_eq!(resolution.discarded, 4);
+    }
+
+    #[test]
+    fn mismatch_checks_the_case_with_0x20() {
+        let args = QueryArgs { randomize_case: true, ..args("ExAmple.com") };
+        let (query, id) = query(&args);
+        let response = answer(&query);
+        let server = SocketAddr::from((Ipv4Addr::LOCALHOST, 53));
+        let check = |response: &[u8]| mismatch(response, server, server, id, &args);
+        assert!(check(&response).is_none());
+        assert!(matches!(check(&altered(&response, |response| response[15] = b'a')), Some(Mismatch::Case)));
+        assert!(matches!(check(&altered(&response, |response| response[13] = b'e')), Some(Mismatch::Case)));
+        assert!(matches!(check(&altered(&response, |response| response[19] = b'f')), Some(Mismatch::Other(_))));
+    }
+
+    #[test]
+    fn falls_back_from_0x20_once_the_case_is_lost() {
+        let (_, server) = loopback();
+        let server_addr = server.local_addr().unwrap();
+        let args = QueryArgs { randomize_case: true, timeout: Duration::from_secs(5), ..args("example.com") };
+        let handle = std::thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let mut queries = Vec::new();
+            let (len, from) = server.recv_from(&mut buffer).unwrap();
+            queries.push(buffer[..len].to_vec());
+            // Flipping the case of every letter makes sure the name differs from the randomized one.
+            let flipped = altered(&answer(&buffer[..len]), |response| {
+                for byte in response[DNSHeader::LEN..len - 4].iter_mut().filter(|byte| byte.is_ascii_alphabetic()) {
+                    *byte ^= 0x20;
+                }
+            });
+            server.send_to(&flipped, from).unwrap();
+            let (len, from) = server.recv_from(&mut buffer).unwrap();
+            queries.push(buffer[..len].to_vec());
+            server.send_to(&answer(&buffer[..len]), from).unwrap();
+            // Nothing else arrives once the server answered.
+            server.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+            assert!(server.recv_from(&mut buffer).is_err());
+            queries
+        });
+        let mut resolution = Resolution::new(&args);
+        let response = send_query(&args, server_addr, &mut resolution).unwrap();
+        let queries = handle.join().unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_ne!(queries[0][..2], queries[1][..2]);
+        assert_eq!(DNSPacket::try_from(queries[1].as_slice()).unwrap().questions[0].name.wire(), args.domain_name.wire());
+        assert_eq!(response.questions[0].name.wire(), args.domain_name.wire());
+        assert_eq!(resolution.discarded, 1);
+        assert!(resolution.case_insensitive.contains(&args.name_server));
     }
-}
\ No newline at end of file
+}
@@ -1,18 +1,29 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddrV4};
 use clap::Parser;
-use rsolver::{Kind, resolve, QueryArgs, LogLevel};
+use rsolver::{Kind, resolve_response, QueryArgs, LogLevel, Resolved, DEFAULT_RETRIES, DEFAULT_TIMEOUT};
+use rsolver::dns_structs::edns::{ClientSubnet, Edns, EdnsOption, DEFAULT_UDP_PAYLOAD_SIZE};
+use rsolver::dns_structs::name::Name;
 use rsolver::enums::Flag;
-use rsolver::errors::ResolverResult;
+use std::fmt::{Display, Formatter};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[clap(version, name = "rsolver", author = "CarlosEduardoL", about = "Simple DNS resolver CLI utility written in Rust")]
 struct Rsolver {
-    #[clap(value_name = "DOMAIN")]
+    #[clap(value_name = "DOMAIN", value_parser = Name::from_idn)]
     /// The domain to resolve
-    domain: String,
+    domain: Name,
     #[clap(long="ns", default_value_t=Ipv4Addr::new(198,41,0,4))]
     /// NameServer IP Address
     name_server: Ipv4Addr,
+    #[clap(long, default_value_t=Ipv4Addr::UNSPECIFIED)]
+    /// The local address to send queries from.
+    source_addr: Ipv4Addr,
+    #[clap(long, default_value_t=0)]
+    /// The local port to send queries from, 0 for a random port for every query.
+    source_port: u16,
     /// The record type
     #[clap(long="type", short='t', default_value_t=Kind::ANY, value_enum)]
     kind: Kind,
@@ -20,22 +31,108 @@ struct Rsolver {
     /// These flags are used to control the behavior of DNS queries and responses.
     flags: Vec<Flag>,
     #[clap(short, default_value_t=LogLevel::None, value_enum)]
-    /// If true shows all the Queries if false just show the result.
-    log_level: LogLevel
+    /// How much to show: none prints just the result, info every query and its status, and debug the
+    /// queries and responses in full.
+    log_level: LogLevel,
+    #[clap(long)]
+    /// Show internationalized domain names (xn--) in the answers in Unicode.
+    unicode: bool,
+    #[clap(long, default_value_t=DEFAULT_UDP_PAYLOAD_SIZE)]
+    /// The UDP payload size advertised with EDNS.
+    bufsize: u16,
+    #[clap(long, conflicts_with_all=["bufsize", "dnssec"])]
+    /// Send plain DNS queries without EDNS.
+    no_edns: bool,
+    #[clap(long)]
+    /// Set the DNSSEC OK (DO) bit to ask for DNSSEC records.
+    dnssec: bool,
+    #[clap(long, value_name = "PREFIX", conflicts_with = "no_edns")]
+    /// Send an EDNS Client Subnet option for this network, like 203.0.113.0/24.
+    subnet: Option<ClientSubnet>,
+    #[clap(long, conflicts_with = "no_edns")]
+    /// Ask the name server for its identifier (NSID).
+    nsid: bool,
+    #[clap(long)]
+    /// Do not send DNS cookies.
+    no_cookie: bool,
+    #[clap(long, value_name = "SECONDS", default_value_t = Seconds(DEFAULT_TIMEOUT))]
+    /// How long to wait for the first response to a query, doubled on every retransmission.
+    timeout: Seconds,
+    #[clap(long, default_value_t = DEFAULT_RETRIES)]
+    /// How many times to retransmit a query that got no response.
+    retries: u32,
+    #[clap(long, value_name = "SECONDS")]
+    /// The longest the whole resolution may take.
+    deadline: Option<Seconds>,
+    #[clap(long = "0x20")]
+    /// Randomize the case of the query name and reject responses that do not echo it (DNS 0x20).
+    randomize_case: bool,
 }
 
-fn main() -> ResolverResult<()> {
+/// A positive number of seconds given on the command line, which may have a fractional part.
+#[derive(Clone, Copy)]
+struct Seconds(Duration);
+
+impl FromStr for Seconds {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.parse::<f64>().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()) {
+            Some(duration) if !duration.is_zero() => Ok(Seconds(duration)),
+            _ => Err(format!("{text:?} is not a positive number of seconds")),
+        }
+    }
+}
+
+impl Display for Seconds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.as_secs_f64())
+    }
+}
+
+fn main() -> ExitCode {
     let cli = Rsolver::parse();
     let args = QueryArgs {
         domain_name: cli.domain,
         name_server: cli.name_server,
+        source: SocketAddrV4::new(cli.source_addr, cli.source_port),
         record_type: cli.kind,
         flags: cli.flags,
         log_level: cli.log_level,
+        edns: (!cli.no_edns).then(|| Edns {
+            udp_payload_size: cli.bufsize,
+            dnssec_ok: cli.dnssec,
+            options: cli.subnet.map(EdnsOption::ClientSubnet).into_iter()
+                .chain(cli.nsid.then(|| EdnsOption::Nsid(Vec::new())))
+                .collect(),
+            ..Edns::default()
+        }),
+        cookies: !cli.no_cookie,
+        timeout: cli.timeout.0,
+        retries: cli.retries,
+        deadline: cli.deadline.map(|deadline| deadline.0),
+        randomize_case: cli.randomize_case,
     };
-    let response = resolve(&args);
-    for answer in response? {
-        println!("{answer}");
+    match resolve_response(&args) {
+        Ok(Resolved { response, discarded }) => {
+            if discarded > 0 {
+                eprintln!("Warning: discarded {discarded} datagrams that did not match the queries");
+            }
+            for option in response.edns().map(|edns| edns.options).unwrap_or_default() {
+                println!("; {option}");
+            }
+            for answer in response.answers.iter().filter(|answer| args.record_type.matches(answer.kind)) {
+                if cli.unicode {
+                    println!("{answer:#}");
+                } else {
+                    println!("{answer}");
+                }
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
     }
-    Ok(())
 }